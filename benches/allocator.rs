@@ -0,0 +1,67 @@
+#![feature(test)]
+
+extern crate cchamt;
+
+extern crate test;
+
+use test::Bencher;
+use std::sync::Arc;
+use std::thread;
+use cchamt::Allocator;
+
+const NTHREAD: usize = 8;
+
+// every thread allocates iter / NTHREAD slots from the same allocator
+fn alloc_from_threads(mem: Arc<Allocator<[u64; 4]>>, iter: usize) {
+    let mut thread_handle: Vec<thread::JoinHandle<_>> = vec![];
+    let step: usize = iter / NTHREAD;
+
+    for _ in 0..NTHREAD {
+        let thread_mem = mem.clone();
+        thread_handle.push(thread::spawn(move || {
+            for i in 0..step {
+                thread_mem.alloc([i as u64; 4]);
+            }
+        }));
+    }
+
+    for thread in thread_handle {
+        thread.join().unwrap();
+    }
+}
+
+#[bench]
+fn bench_100k_alloc_shared_counter(b: &mut Bencher) {
+    let iter = 100000;
+    b.iter(|| {
+        let mem = Arc::new(Allocator::with_chunk_size(iter + 256 * 64, 1));
+        alloc_from_threads(mem, iter);
+    });
+}
+
+#[bench]
+fn bench_100k_alloc_thread_buffers(b: &mut Bencher) {
+    let iter = 100000;
+    b.iter(|| {
+        let mem = Arc::new(Allocator::new(iter + 256 * 64));
+        alloc_from_threads(mem, iter);
+    });
+}
+
+#[bench]
+fn bench_million_alloc_shared_counter(b: &mut Bencher) {
+    let iter = 1000000;
+    b.iter(|| {
+        let mem = Arc::new(Allocator::with_chunk_size(iter + 256 * 64, 1));
+        alloc_from_threads(mem, iter);
+    });
+}
+
+#[bench]
+fn bench_million_alloc_thread_buffers(b: &mut Bencher) {
+    let iter = 1000000;
+    b.iter(|| {
+        let mem = Arc::new(Allocator::new(iter + 256 * 64));
+        alloc_from_threads(mem, iter);
+    });
+}
@@ -0,0 +1,66 @@
+#![feature(test)]
+
+extern crate cchamt;
+
+extern crate test;
+
+use test::Bencher;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use cchamt::{AtomicContiguousTrie, ContiguousTrie};
+
+const NTHREAD: usize = 4;
+
+// NTHREAD threads each run $op on range / NTHREAD keys
+macro_rules! vs {
+    ($trie:expr, $range:expr, |$t:ident, $i:ident| $op:expr) => {{
+        let mut thread_handle: Vec<thread::JoinHandle<_>> = vec![];
+        let step = $range / NTHREAD as u32;
+        for t_id in 0..NTHREAD as u32 {
+            let $t = $trie.clone();
+            thread_handle.push(thread::spawn(move || {
+                for $i in (t_id * step)..((t_id + 1) * step) {
+                    let _g = $op;
+                }
+            }));
+        }
+        for thread in thread_handle {
+            thread.join().unwrap();
+        }
+    }};
+}
+
+#[bench]
+fn bench_vs_get_1000000_atomic(b: &mut Bencher) {
+    let trie = Arc::new(AtomicContiguousTrie::<u32, u32>::new(32, 8, 1 << 21));
+    for i in 0..1000000u32 {
+        trie.insert(i, i).unwrap();
+    }
+    b.iter(|| vs!(trie, 1000000, |trie, i| trie.get(i)));
+}
+
+#[bench]
+fn bench_vs_get_1000000_rwlock(b: &mut Bencher) {
+    let mut inner = ContiguousTrie::<u32, u32>::new(32, 8);
+    for i in 0..1000000u32 {
+        inner.insert(i, i).unwrap();
+    }
+    let trie = Arc::new(RwLock::new(inner));
+    b.iter(|| vs!(trie, 1000000, |trie, i| trie.read().unwrap().get(i)));
+}
+
+#[bench]
+fn bench_vs_insert_1000000_atomic(b: &mut Bencher) {
+    b.iter(|| {
+        let trie = Arc::new(AtomicContiguousTrie::<u32, u32>::new(32, 8, 1 << 21));
+        vs!(trie, 1000000, |trie, i| trie.insert(i, i));
+    });
+}
+
+#[bench]
+fn bench_vs_insert_1000000_rwlock(b: &mut Bencher) {
+    b.iter(|| {
+        let trie = Arc::new(RwLock::new(ContiguousTrie::<u32, u32>::new(32, 8)));
+        vs!(trie, 1000000, |trie, i| trie.write().unwrap().insert(i, i));
+    });
+}
@@ -11,20 +11,16 @@
 //
 //#[bench]
 //fn bench_10_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 10;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[i][2..]);
+//            let _g = trie.get(i as u32);
 //        }
 //    });
 //}
@@ -32,20 +28,16 @@
 //
 //#[bench]
 //fn bench_100_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 100;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[i][2..]);
+//            let _g = trie.get(i as u32);
 //        }
 //    });
 //}
@@ -53,40 +45,32 @@
 //
 //#[bench]
 //fn bench_1k_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 1000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[i][2..]);
+//            let _g = trie.get(i as u32);
 //        }
 //    });
 //}
 //
 //#[bench]
 //fn bench_10k_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 10000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[i][2..]);
+//            let _g = trie.get(i as u32);
 //        }
 //    });
 //}
@@ -94,40 +78,32 @@
 //
 //#[bench]
 //fn bench_100k_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 100000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[i][2..]);
+//            let _g = trie.get(i as u32);
 //        }
 //    });
 //}
 //
 //#[bench]
 //fn bench_million_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 1000000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[i][2..]);
+//            let _g = trie.get(i as u32);
 //        }
 //    });
 //}
@@ -135,20 +111,16 @@
 //
 //#[bench]
 //fn bench_10_million_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 10000000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[i][2..]);
+//            let _g = trie.get(i as u32);
 //        }
 //    });
 //}
@@ -156,20 +128,16 @@
 //
 //#[bench]
 //fn bench_100_million_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 10000000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[i][2..]);
+//            let _g = trie.get(i as u32);
 //        }
 //    });
 //}
@@ -177,20 +145,16 @@
 //
 //#[bench]
 //fn bench_1000_million_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 100000000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[i][2..]);
+//            let _g = trie.get(i as u32);
 //        }
 //    });
 //}
\ No newline at end of file
@@ -41,7 +41,7 @@
 //                for i in begin..end {
 //                    let str = binary_format!(i);
 //                    let arr = str.to_owned().into_bytes();
-//                    thread_trie.insert(i, &arr[2..]);
+//                    thread_trie.insert(i, &arr[2..]).unwrap();
 //                }
 //            }));
 //        }
@@ -87,7 +87,7 @@
 //                for i in begin..end {
 //                    let str = binary_format!(i);
 //                    let arr = str.to_owned().into_bytes();
-//                    assert_eq!(thread_trie.get(&arr[2..]).unwrap(), i);
+//                    assert_eq!(thread_trie.get(&arr[2..]), Ok(Some(i)));
 //                }
 //            }));
 //        }
@@ -130,7 +130,7 @@
 //                for i in begin..end {
 //                    let str = binary_format!(i);
 //                    let arr = str.to_owned().into_bytes();
-//                    thread_trie.insert(i, &arr[2..]);
+//                    thread_trie.insert(i, &arr[2..]).unwrap();
 //                }
 //            }));
 //        }
@@ -176,7 +176,7 @@
 //                for i in begin..end {
 //                    let str = binary_format!(i);
 //                    let arr = str.to_owned().into_bytes();
-//                    assert_eq!(thread_trie.get(&arr[2..]).unwrap(), i);
+//                    assert_eq!(thread_trie.get(&arr[2..]), Ok(Some(i)));
 //                }
 //            }));
 //        }
@@ -218,7 +218,7 @@
 //                for i in begin..end {
 //                    let str = binary_format!(i);
 //                    let arr = str.to_owned().into_bytes();
-//                    thread_trie.insert(i, &arr[2..]);
+//                    thread_trie.insert(i, &arr[2..]).unwrap();
 //                }
 //            }));
 //        }
@@ -264,7 +264,7 @@
 //                for i in begin..end {
 //                    let str = binary_format!(i);
 //                    let arr = str.to_owned().into_bytes();
-//                    assert_eq!(thread_trie.get(&arr[2..]).unwrap(), i);
+//                    assert_eq!(thread_trie.get(&arr[2..]), Ok(Some(i)));
 //                }
 //            }));
 //        }
@@ -305,7 +305,7 @@
 //                for i in begin..end {
 //                    let str = binary_format!(i);
 //                    let arr = str.to_owned().into_bytes();
-//                    thread_trie.insert(i, &arr[2..]);
+//                    thread_trie.insert(i, &arr[2..]).unwrap();
 //                }
 //            }));
 //        }
@@ -351,7 +351,7 @@
 //                for i in begin..end {
 //                    let str = binary_format!(i);
 //                    let arr = str.to_owned().into_bytes();
-//                    assert_eq!(thread_trie.get(&arr[2..]).unwrap(), i);
+//                    assert_eq!(thread_trie.get(&arr[2..]), Ok(Some(i)));
 //                }
 //            }));
 //        }
@@ -393,7 +393,7 @@
 //                for i in begin..end {
 //                    let str = binary_format!(i);
 //                    let arr = str.to_owned().into_bytes();
-//                    thread_trie.insert(i, &arr[2..]);
+//                    thread_trie.insert(i, &arr[2..]).unwrap();
 //                }
 //            }));
 //        }
@@ -439,7 +439,7 @@
 //                for i in begin..end {
 //                    let str = binary_format!(i);
 //                    let arr = str.to_owned().into_bytes();
-//                    assert_eq!(thread_trie.get(&arr[2..]).unwrap(), i);
+//                    assert_eq!(thread_trie.get(&arr[2..]), Ok(Some(i)));
 //                }
 //            }));
 //        }
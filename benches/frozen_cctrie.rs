@@ -0,0 +1,90 @@
+#![feature(test)]
+
+extern crate cchamt;
+
+extern crate test;
+
+use test::Bencher;
+use std::sync::Arc;
+use std::thread;
+use cchamt::{ContiguousTrie, FrozenContiguousTrie};
+
+const NTHREAD: usize = 4;
+
+fn build(range: u32) -> ContiguousTrie<u32, u32> {
+    let mut trie = ContiguousTrie::<u32, u32>::new(32, 8);
+    for i in 0..range {
+        trie.insert(i, i).unwrap();
+    }
+    trie
+}
+
+// same workload as dump/vs_get_*: NTHREAD threads each get range / NTHREAD keys
+macro_rules! vs_get {
+    ($trie:expr, $range:expr) => {{
+        let mut thread_handle: Vec<thread::JoinHandle<_>> = vec![];
+        let step = $range / NTHREAD as u32;
+        for t_id in 0..NTHREAD as u32 {
+            let thread_trie = $trie.clone();
+            thread_handle.push(thread::spawn(move || {
+                for i in (t_id * step)..((t_id + 1) * step) {
+                    let _g = thread_trie.get(i);
+                }
+            }));
+        }
+        for thread in thread_handle {
+            thread.join().unwrap();
+        }
+    }};
+}
+
+#[bench]
+fn bench_vs_get_100000_trie(b: &mut Bencher) {
+    let trie = Arc::new(build(100000));
+    b.iter(|| vs_get!(trie, 100000));
+}
+
+#[bench]
+fn bench_vs_get_100000_frozen(b: &mut Bencher) {
+    let trie: Arc<FrozenContiguousTrie<u32, u32>> = Arc::new(build(100000).freeze());
+    b.iter(|| vs_get!(trie, 100000));
+}
+
+#[bench]
+fn bench_vs_get_1000000_trie(b: &mut Bencher) {
+    let trie = Arc::new(build(1000000));
+    b.iter(|| vs_get!(trie, 1000000));
+}
+
+#[bench]
+fn bench_vs_get_1000000_frozen(b: &mut Bencher) {
+    let trie: Arc<FrozenContiguousTrie<u32, u32>> = Arc::new(build(1000000).freeze());
+    b.iter(|| vs_get!(trie, 1000000));
+}
+
+// keys looked up in a scattered order, so that most lookups miss the cache
+fn scattered(range: u32) -> Vec<u32> {
+    (0..range).map(|i| ((i as u64 * 2654435761) % range as u64) as u32).collect()
+}
+
+#[bench]
+fn bench_rand_get_1000000_trie(b: &mut Bencher) {
+    let trie = build(1000000);
+    let order = scattered(1000000);
+    b.iter(|| {
+        for &i in order.iter() {
+            let _g = trie.get(i);
+        }
+    });
+}
+
+#[bench]
+fn bench_rand_get_1000000_frozen(b: &mut Bencher) {
+    let trie = build(1000000).freeze();
+    let order = scattered(1000000);
+    b.iter(|| {
+        for &i in order.iter() {
+            let _g = trie.get(i);
+        }
+    });
+}
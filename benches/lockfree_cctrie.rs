@@ -8,7 +8,7 @@ extern crate test;
 use test::Bencher;
 use std::usize;
 use std::collections::HashMap;
-use cchamt::LockfreeTrie;
+use cchamt::{LockfreeTrie, BoxAllocator, MmapAllocator};
 
 
 #[bench]
@@ -59,6 +59,100 @@ fn bench_100k_get_trie(b: &mut Bencher) {
     });
 }
 
+// same as bench_100k_get_trie, with the lookups sampled for repack
+#[bench]
+fn bench_100k_get_profiling_trie(b: &mut Bencher) {
+    let mut trie = LockfreeTrie::<usize,usize>::new();
+    let range = 100000;
+
+    for i in 0..range {
+        trie.insert(i, i+1);
+    }
+
+    trie.start_profiling();
+    b.iter(|| {
+        for i in 0..range {
+            let _g = trie.lookup(&i);
+        }
+    });
+}
+
+#[bench]
+fn bench_100k_get_many_trie(b: &mut Bencher) {
+    let mut trie = LockfreeTrie::<usize,usize>::new();
+    let range = 100000;
+
+    for i in 0..range {
+        trie.insert(i, i+1);
+    }
+
+    let keys: Vec<usize> = (0..range).collect();
+    b.iter(|| {
+        for batch in keys.chunks(256) {
+            let _g = trie.get_many(batch);
+        }
+    });
+}
+
+// same as bench_100k_get_trie, with every node in its own Box instead of the arena
+#[bench]
+fn bench_100k_get_box_trie(b: &mut Bencher) {
+    let mut trie = LockfreeTrie::<usize,usize,_>::with_allocator(BoxAllocator::new());
+    let range = 100000;
+
+    for i in 0..range {
+        trie.insert(i, i+1);
+    }
+
+    b.iter(|| {
+        for i in 0..range {
+            let _g = trie.lookup(&i);
+        }
+    });
+}
+
+#[bench]
+fn bench_100k_get_mmap_trie(b: &mut Bencher) {
+    let mut trie = LockfreeTrie::<usize,usize,_>::with_allocator(MmapAllocator::new(1 << 24));
+    let range = 100000;
+
+    for i in 0..range {
+        trie.insert(i, i+1);
+    }
+
+    b.iter(|| {
+        for i in 0..range {
+            let _g = trie.lookup(&i);
+        }
+    });
+}
+
+// lookups of a small hot set of keys, after repack moved their paths together
+#[bench]
+fn bench_100k_get_hot_repacked_trie(b: &mut Bencher) {
+    let mut trie = LockfreeTrie::<usize,usize>::new();
+    let range = 100000;
+
+    for i in 0..range {
+        trie.insert(i, i+1);
+    }
+
+    let hot: Vec<usize> = (0..1024).map(|i| (i * 7919) % range).collect();
+    trie.start_profiling();
+    for _ in 0..100 {
+        for i in hot.iter() {
+            let _g = trie.lookup(i);
+        }
+    }
+    trie.stop_profiling();
+    trie.repack(4096);
+
+    b.iter(|| {
+        for i in hot.iter() {
+            let _g = trie.lookup(i);
+        }
+    });
+}
 
 #[bench]
 fn bench_100k_get_hashmap(b: &mut Bencher) {
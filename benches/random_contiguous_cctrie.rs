@@ -15,24 +15,21 @@
 //
 //#[bench]
 //fn bench_10_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = vec![];
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 10;
+//    let mut v: Vec<u32> = (0..range).collect();
 //    {
-//        let slice: &mut [Vec<u8>] = v.as_mut_slice();
+//        let slice: &mut [u32] = v.as_mut_slice();
 //        thread_rng().shuffle(slice);
 //    }
 //
-//    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//    for i in &v {
+//        trie.insert(*i as usize, *i).unwrap();
 //    }
 //
 //    b.iter(|| {
-//        for arr in &v {
-//            let _g = trie.get(&arr[2..]);
+//        for i in &v {
+//            let _g = trie.get(*i);
 //        }
 //    });
 //}
@@ -61,24 +58,21 @@
 //
 //#[bench]
 //fn bench_100_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = vec![];
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 100;
+//    let mut v: Vec<u32> = (0..range).collect();
 //    {
-//        let slice: &mut [Vec<u8>] = v.as_mut_slice();
+//        let slice: &mut [u32] = v.as_mut_slice();
 //        thread_rng().shuffle(slice);
 //    }
 //
-//    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//    for i in &v {
+//        trie.insert(*i as usize, *i).unwrap();
 //    }
 //
 //    b.iter(|| {
-//        for arr in &v {
-//            let _g = trie.get(&arr[2..]);
+//        for i in &v {
+//            let _g = trie.get(*i);
 //        }
 //    });
 //}
@@ -107,24 +101,21 @@
 //
 //#[bench]
 //fn bench_1000_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = vec![];
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 1000;
+//    let mut v: Vec<u32> = (0..range).collect();
 //    {
-//        let slice: &mut [Vec<u8>] = v.as_mut_slice();
+//        let slice: &mut [u32] = v.as_mut_slice();
 //        thread_rng().shuffle(slice);
 //    }
 //
-//    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//    for i in &v {
+//        trie.insert(*i as usize, *i).unwrap();
 //    }
 //
 //    b.iter(|| {
-//        for arr in &v {
-//            let _g = trie.get(&arr[2..]);
+//        for i in &v {
+//            let _g = trie.get(*i);
 //        }
 //    });
 //}
@@ -153,24 +144,21 @@
 //
 //#[bench]
 //fn bench_10000_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = vec![];
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 10000;
+//    let mut v: Vec<u32> = (0..range).collect();
 //    {
-//        let slice: &mut [Vec<u8>] = v.as_mut_slice();
+//        let slice: &mut [u32] = v.as_mut_slice();
 //        thread_rng().shuffle(slice);
 //    }
 //
-//    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//    for i in &v {
+//        trie.insert(*i as usize, *i).unwrap();
 //    }
 //
 //    b.iter(|| {
-//        for arr in &v {
-//            let _g = trie.get(&arr[2..]);
+//        for i in &v {
+//            let _g = trie.get(*i);
 //        }
 //    });
 //}
@@ -199,24 +187,21 @@
 //
 //#[bench]
 //fn bench_100000_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = vec![];
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 100000;
+//    let mut v: Vec<u32> = (0..range).collect();
 //    {
-//        let slice: &mut [Vec<u8>] = v.as_mut_slice();
+//        let slice: &mut [u32] = v.as_mut_slice();
 //        thread_rng().shuffle(slice);
 //    }
 //
-//    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//    for i in &v {
+//        trie.insert(*i as usize, *i).unwrap();
 //    }
 //
 //    b.iter(|| {
-//        for arr in &v {
-//            let _g = trie.get(&arr[2..]);
+//        for i in &v {
+//            let _g = trie.get(*i);
 //        }
 //    });
 //}
@@ -245,24 +230,21 @@
 //
 //#[bench]
 //fn bench_1000000_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = vec![];
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 1000000;
+//    let mut v: Vec<u32> = (0..range).collect();
 //    {
-//        let slice: &mut [Vec<u8>] = v.as_mut_slice();
+//        let slice: &mut [u32] = v.as_mut_slice();
 //        thread_rng().shuffle(slice);
 //    }
 //
-//    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//    for i in &v {
+//        trie.insert(*i as usize, *i).unwrap();
 //    }
 //
 //    b.iter(|| {
-//        for arr in &v {
-//            let _g = trie.get(&arr[2..]);
+//        for i in &v {
+//            let _g = trie.get(*i);
 //        }
 //    });
 //}
@@ -291,24 +273,21 @@
 //
 //#[bench]
 //fn bench_10000000_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = vec![];
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 10000000;
+//    let mut v: Vec<u32> = (0..range).collect();
 //    {
-//        let slice: &mut [Vec<u8>] = v.as_mut_slice();
+//        let slice: &mut [u32] = v.as_mut_slice();
 //        thread_rng().shuffle(slice);
 //    }
 //
-//    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//    for i in &v {
+//        trie.insert(*i as usize, *i).unwrap();
 //    }
 //
 //    b.iter(|| {
-//        for arr in &v {
-//            let _g = trie.get(&arr[2..]);
+//        for i in &v {
+//            let _g = trie.get(*i);
 //        }
 //    });
 //}
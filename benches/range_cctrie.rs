@@ -0,0 +1,140 @@
+#![feature(test)]
+
+extern crate cchamt;
+
+extern crate test;
+
+use test::Bencher;
+use std::collections::BTreeMap;
+use cchamt::ContiguousTrie;
+
+const NPREFIXES: u32 = 64;
+const NSCANS: u32 = 1000;
+
+// the /16 prefixes in use, spread over the key space
+fn prefixes() -> Vec<u32> {
+    (0..NPREFIXES).map(|i| i.wrapping_mul(2654435761) >> 16).collect()
+}
+
+// clustered keys, like the addresses of a routing table: every stride-th address of the first
+// quarter of each prefix in use
+fn keys(stride: u32) -> Vec<u32> {
+    let mut keys = Vec::new();
+    for p in prefixes() {
+        for j in 0..(1 << 14) / stride {
+            keys.push(p << 16 | j * stride);
+        }
+    }
+    keys
+}
+
+fn build_trie(stride: u32) -> ContiguousTrie<u32, u32> {
+    let mut trie = ContiguousTrie::<u32, u32>::new(32, 8);
+    for k in keys(stride) {
+        trie.insert(k, k).unwrap();
+    }
+    trie
+}
+
+fn build_btree(stride: u32) -> BTreeMap<u32, u32> {
+    keys(stride).into_iter().map(|k| (k, k)).collect()
+}
+
+// the first keys of NSCANS scans of width keys each, within the prefixes in use
+fn starts(width: u32) -> Vec<u32> {
+    let prefixes = prefixes();
+    (0..NSCANS).map(|i| {
+        let k = prefixes[(i % NPREFIXES) as usize] << 16 | (i.wrapping_mul(2654435761) & 0x3fff);
+        k & !(width - 1)
+    }).collect()
+}
+
+fn scan_trie(trie: &ContiguousTrie<u32, u32>, starts: &[u32], width: u32) -> u64 {
+    let mut sum = 0;
+    for &lo in starts.iter() {
+        for (_, v) in trie.range(lo..=lo | (width - 1)) {
+            sum += v as u64;
+        }
+    }
+    sum
+}
+
+fn scan_btree(btree: &BTreeMap<u32, u32>, starts: &[u32], width: u32) -> u64 {
+    let mut sum = 0;
+    for &lo in starts.iter() {
+        for (_, &v) in btree.range(lo..=lo | (width - 1)) {
+            sum += v as u64;
+        }
+    }
+    sum
+}
+
+#[bench]
+fn bench_range_2_power_16_dense_trie(b: &mut Bencher) {
+    let trie = build_trie(1);
+    let starts = starts(1 << 16);
+    b.iter(|| scan_trie(&trie, &starts, 1 << 16));
+}
+
+#[bench]
+fn bench_range_2_power_16_dense_btree(b: &mut Bencher) {
+    let btree = build_btree(1);
+    let starts = starts(1 << 16);
+    b.iter(|| scan_btree(&btree, &starts, 1 << 16));
+}
+
+#[bench]
+fn bench_range_2_power_16_sparse_trie(b: &mut Bencher) {
+    let trie = build_trie(4);
+    let starts = starts(1 << 16);
+    b.iter(|| scan_trie(&trie, &starts, 1 << 16));
+}
+
+#[bench]
+fn bench_range_2_power_16_sparse_btree(b: &mut Bencher) {
+    let btree = build_btree(4);
+    let starts = starts(1 << 16);
+    b.iter(|| scan_btree(&btree, &starts, 1 << 16));
+}
+
+#[bench]
+fn bench_range_2_power_8_dense_trie(b: &mut Bencher) {
+    let trie = build_trie(1);
+    let starts = starts(1 << 8);
+    b.iter(|| scan_trie(&trie, &starts, 1 << 8));
+}
+
+#[bench]
+fn bench_range_2_power_8_dense_btree(b: &mut Bencher) {
+    let btree = build_btree(1);
+    let starts = starts(1 << 8);
+    b.iter(|| scan_btree(&btree, &starts, 1 << 8));
+}
+
+// same scans as range_2_power_16_dense_trie, given as /16 prefixes
+#[bench]
+fn bench_prefix_16_dense_trie(b: &mut Bencher) {
+    let trie = build_trie(1);
+    let starts = starts(1 << 16);
+    b.iter(|| {
+        let mut sum = 0u64;
+        for &lo in starts.iter() {
+            for (_, v) in trie.prefix(lo >> 16, 16) {
+                sum += v as u64;
+            }
+        }
+        sum
+    });
+}
+
+#[bench]
+fn bench_iter_dense_trie(b: &mut Bencher) {
+    let trie = build_trie(1);
+    b.iter(|| trie.values().fold(0u64, |sum, v| sum + v as u64));
+}
+
+#[bench]
+fn bench_iter_dense_btree(b: &mut Bencher) {
+    let btree = build_btree(1);
+    b.iter(|| btree.values().fold(0u64, |sum, &v| sum + v as u64));
+}
@@ -12,20 +12,16 @@
 //
 //#[bench]
 //fn bench_rev_10_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 10;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[range-i][2..]);
+//            let _g = trie.get((range - i) as u32);
 //        }
 //    });
 //}
@@ -48,20 +44,16 @@
 //
 //#[bench]
 //fn bench_rev_100_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 100;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[range-i][2..]);
+//            let _g = trie.get((range - i) as u32);
 //        }
 //    });
 //}
@@ -84,20 +76,16 @@
 //
 //#[bench]
 //fn bench_rev_1000_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 1000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[range-i][2..]);
+//            let _g = trie.get((range - i) as u32);
 //        }
 //    });
 //}
@@ -120,20 +108,16 @@
 //
 //#[bench]
 //fn bench_rev_10000_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 10000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[range-i][2..]);
+//            let _g = trie.get((range - i) as u32);
 //        }
 //    });
 //}
@@ -156,20 +140,16 @@
 //
 //#[bench]
 //fn bench_rev_100000_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 100000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[range-i][2..]);
+//            let _g = trie.get((range - i) as u32);
 //        }
 //    });
 //}
@@ -192,20 +172,16 @@
 //
 //#[bench]
 //fn bench_rev_1000000_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 1000000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[range-i][2..]);
+//            let _g = trie.get((range - i) as u32);
 //        }
 //    });
 //}
@@ -228,20 +204,16 @@
 //
 //#[bench]
 //fn bench_rev_10000000_get_trie(b: &mut Bencher) {
-//    let mut trie = ContiguousTrie::<usize>::new(32, 8);
-//    let mut v: Vec<Vec<u8>> = Vec::new();
+//    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 //    let range = 10000000;
 //
 //    for i in 0..range {
-//        let str = binary_format!(i);
-//        let arr = str.to_owned().into_bytes();
-//        v.push(arr.clone());
-//        trie.insert(i, &arr[2..]);
+//        trie.insert(i, i as u32).unwrap();
 //    }
 //
 //    b.iter(|| {
 //        for i in 0..range {
-//            let _g = trie.get(&v[range-i][2..]);
+//            let _g = trie.get((range - i) as u32);
 //        }
 //    });
 //}
@@ -0,0 +1,56 @@
+#![feature(test)]
+
+#[macro_use]
+extern crate cchamt;
+
+extern crate test;
+
+use test::Bencher;
+use std::sync::Arc;
+use std::thread;
+use cchamt::RwContiguousTrie;
+
+const NTHREAD: usize = 4;
+const RANGE: usize = 100000;
+
+// a trie holding the keys 0..RANGE, and the keys as 24 '0'/'1' digits
+fn build() -> (Arc<RwContiguousTrie<usize>>, Arc<Vec<Vec<u8>>>) {
+    let keys: Vec<Vec<u8>> = (0..RANGE).map(|i| binary_format!(i).into_bytes()[10..].to_vec()).collect();
+    let trie = RwContiguousTrie::<usize>::new(24, 8);
+    for (i, key) in keys.iter().enumerate() {
+        trie.insert(i, key).unwrap();
+    }
+    (Arc::new(trie), Arc::new(keys))
+}
+
+// NTHREAD threads each look up RANGE / NTHREAD keys with $get
+macro_rules! vs_get {
+    ($trie:expr, $keys:expr, $get:ident) => {{
+        let mut thread_handle: Vec<thread::JoinHandle<_>> = vec![];
+        let step = RANGE / NTHREAD;
+        for t_id in 0..NTHREAD {
+            let trie = $trie.clone();
+            let keys = $keys.clone();
+            thread_handle.push(thread::spawn(move || {
+                for i in (t_id * step)..((t_id + 1) * step) {
+                    let _g = trie.$get(&keys[i]);
+                }
+            }));
+        }
+        for thread in thread_handle {
+            thread.join().unwrap();
+        }
+    }};
+}
+
+#[bench]
+fn bench_vs_get_100000_locked(b: &mut Bencher) {
+    let (trie, keys) = build();
+    b.iter(|| vs_get!(trie, keys, get));
+}
+
+#[bench]
+fn bench_vs_get_100000_optimistic(b: &mut Bencher) {
+    let (trie, keys) = build();
+    b.iter(|| vs_get!(trie, keys, get_optimistic));
+}
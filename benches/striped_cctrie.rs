@@ -0,0 +1,91 @@
+#![feature(test)]
+
+#[macro_use]
+extern crate cchamt;
+
+extern crate test;
+
+use test::Bencher;
+use std::sync::Arc;
+use std::thread;
+use cchamt::{MutexContiguousTrie, RwContiguousTrie, StripedContiguousTrie};
+
+const NTHREAD: usize = 4;
+const RANGE: usize = 100000;
+
+// the keys 0..RANGE as 24 '0'/'1' digits, formatted once so the benches only time the tries
+fn keys() -> Arc<Vec<Vec<u8>>> {
+    Arc::new((0..RANGE).map(|i| binary_format!(i).into_bytes()[10..].to_vec()).collect())
+}
+
+// NTHREAD threads each run $op on RANGE / NTHREAD keys
+macro_rules! vs {
+    ($trie:expr, $keys:expr, |$t:ident, $i:ident, $key:ident| $op:expr) => {{
+        let mut thread_handle: Vec<thread::JoinHandle<_>> = vec![];
+        let step = RANGE / NTHREAD;
+        for t_id in 0..NTHREAD {
+            let $t = $trie.clone();
+            let keys = $keys.clone();
+            thread_handle.push(thread::spawn(move || {
+                for $i in (t_id * step)..((t_id + 1) * step) {
+                    let $key = &keys[$i][..];
+                    let _g = $op;
+                }
+            }));
+        }
+        for thread in thread_handle {
+            thread.join().unwrap();
+        }
+    }};
+}
+
+#[bench]
+fn bench_vs_insert_100000_striped(b: &mut Bencher) {
+    let keys = keys();
+    b.iter(|| {
+        let trie = Arc::new(StripedContiguousTrie::<usize>::new(24, 8));
+        vs!(trie, keys, |trie, i, key| trie.insert(i, key));
+    });
+}
+
+#[bench]
+fn bench_vs_insert_100000_mutex(b: &mut Bencher) {
+    let keys = keys();
+    b.iter(|| {
+        let trie = Arc::new(MutexContiguousTrie::<usize>::new(24, 8));
+        vs!(trie, keys, |trie, i, key| trie.insert(i, key));
+    });
+}
+
+#[bench]
+fn bench_vs_insert_100000_rwlock(b: &mut Bencher) {
+    let keys = keys();
+    b.iter(|| {
+        let trie = Arc::new(RwContiguousTrie::<usize>::new(24, 8));
+        vs!(trie, keys, |trie, i, key| trie.insert(i, key));
+    });
+}
+
+#[bench]
+fn bench_vs_get_100000_striped(b: &mut Bencher) {
+    let keys = keys();
+    let trie = Arc::new(StripedContiguousTrie::<usize>::new(24, 8));
+    vs!(trie, keys, |trie, i, key| trie.insert(i, key));
+    b.iter(|| vs!(trie, keys, |trie, i, key| trie.get(key)));
+}
+
+#[bench]
+fn bench_vs_get_100000_mutex(b: &mut Bencher) {
+    let keys = keys();
+    let trie = Arc::new(MutexContiguousTrie::<usize>::new(24, 8));
+    vs!(trie, keys, |trie, i, key| trie.insert(i, key));
+    b.iter(|| vs!(trie, keys, |trie, i, key| trie.get(key)));
+}
+
+#[bench]
+fn bench_vs_get_100000_rwlock(b: &mut Bencher) {
+    let keys = keys();
+    let trie = Arc::new(RwContiguousTrie::<usize>::new(24, 8));
+    vs!(trie, keys, |trie, i, key| trie.insert(i, key));
+    b.iter(|| vs!(trie, keys, |trie, i, key| trie.get(key)));
+}
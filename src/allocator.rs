@@ -1,26 +1,647 @@
 extern crate libc;
-use std::sync::atomic::{AtomicUsize,Ordering};
+use std::sync::atomic::{AtomicUsize, AtomicU64, AtomicPtr, Ordering};
+use std::sync::Mutex;
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::cmp;
+use std::error::Error;
+use std::fmt;
 use std::mem;
+use std::ptr;
+
+//number of slots a thread reserves from the arena at a time
+const CHUNK_SIZE: usize = 256;
+//number of per-thread buffers; threads beyond that share a buffer
+const NBUFFERS: usize = 64;
+//cursor value of a buffer that is being refilled
+const REFILLING: u64 = u64::max_value();
+//slot index that ends a free list
+const NIL: u64 = 0xffff_ffff;
+
+//granularity at which the kernel backs the arena with memory
+const PAGE_SIZE: usize = 4096;
+//byte written over the slots DebugAllocator frees
+const POISON: u8 = 0xde;
+
+//hands out a small sequential id per thread, used to pick its buffer
+static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local!(static THREAD_ID: usize = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+
+//chunk of the arena reserved by one thread, like a TLAB
+//cursor packs the next free slot in its low 32 bits and the end of the chunk in its high 32 bits,
+//so taking a slot is a CAS on a cache line that no other thread normally touches
+//retired is a lock-free stack of the slots this thread retired; they still hold live values, so
+//unlike the free list they can't be linked through the slots themselves
+#[repr(align(64))]
+struct ThreadBuffer {
+    cursor: AtomicU64,
+    retired: AtomicPtr<Retired>,
+}
+
+//entry of a retired stack
+//threads only ever push onto it, and it is emptied under &mut self, so unlike the free list
+//the head needs no ABA tag
+struct Retired {
+    slot: usize,
+    next: *mut Retired,
+}
+
+//where the arena memory comes from
+enum Storage {
+    Calloc,
+    Mmap(usize), //length of the mapping
+}
 
 pub struct Allocator<T> {
     buf: *mut T,
+    raw: *mut libc::c_void, //start of the reserved memory, buf is rounded up from it to T's alignment
+    storage: Storage,
     capacity: usize,
-    n: AtomicUsize 
+    n: AtomicUsize, //slots reserved from the arena so far
+    chunk: usize,
+    buffers: Vec<ThreadBuffer>,
+    //lock-free stack of freed slot indices, linked through the first 4 bytes of each slot
+    //the head packs the top slot in its low 32 bits and an ABA tag in its high 32 bits
+    //an Allocator<T> only serves one slot size, so one free list covers all of its slots
+    free: AtomicU64,
+    nfree: AtomicUsize, //number of slots on the free list
+    nretired: AtomicUsize, //number of slots waiting in the retired lists
+}
+
+//returned by try_alloc when the allocator has no slot left
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocError {
+    CapacityExceeded,
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
 }
 
+impl Error for AllocError {
+    fn description(&self) -> &str {
+        match *self {
+            AllocError::CapacityExceeded => "allocator capacity exceeded",
+        }
+    }
+}
+
+//snapshot of how much of an allocator is used, see NodeAllocator::usage
+//taken while other threads allocate, the counts may be slightly off
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub in_use: usize, //slots holding a value, retired ones included
+    pub retired: usize, //slots waiting for the next reclaim
+    pub high_water: usize, //peak of in_use; for an arena, the slots taken from it so far
+    pub reserved_bytes: usize, //memory reserved up front
+    pub touched_bytes: usize, //memory written so far, roughly what is resident
+}
+
+unsafe impl<T: Send> Send for Allocator<T> {}
+unsafe impl<T: Send> Sync for Allocator<T> {}
+
 impl<T> Allocator<T> {
     pub fn new(size: usize) -> Self {
+        Allocator::with_chunk_size(size, CHUNK_SIZE)
+    }
+
+    //arena backed by an anonymous mapping: the size only reserves address space, and the kernel
+    //backs the pages with memory as the slots are first written
+    pub fn reserve(size: usize) -> Self {
+        Allocator::with_storage(size, CHUNK_SIZE, Storage::Mmap(0))
+    }
+
+    //chunk: number of slots each thread reserves at a time; 1 reserves every slot from the shared counter
+    pub fn with_chunk_size(size: usize, chunk: usize) -> Self {
+        Allocator::with_storage(size, chunk, Storage::Calloc)
+    }
+
+    fn with_storage(size: usize, chunk: usize, storage: Storage) -> Self {
+        assert!(size < u32::max_value() as usize);
+        assert!(chunk > 0);
+        assert!(mem::size_of::<T>() >= mem::size_of::<u32>());
+        let mut buffers = Vec::with_capacity(NBUFFERS);
+        for _ in 0..NBUFFERS {
+            buffers.push(ThreadBuffer { cursor: AtomicU64::new(0), retired: AtomicPtr::new(ptr::null_mut()) });
+        }
+        //calloc only guarantees 16-byte alignment, so reserve one extra alignment unit and round up
+        let align = mem::align_of::<T>();
+        let bytes = size.checked_mul(mem::size_of::<T>()).expect("arena size overflows usize") + align;
+        let (raw, storage) = match storage {
+            Storage::Calloc => {
+                let raw = unsafe {libc::calloc(bytes as libc::size_t, 1)};
+                assert!(!raw.is_null(), "failed to reserve an arena of {} slots", size);
+                (raw, Storage::Calloc)
+            }
+            Storage::Mmap(_) => {
+                //anonymous pages are zeroed and only backed by memory once touched
+                let raw = unsafe {libc::mmap(ptr::null_mut(), bytes as libc::size_t, libc::PROT_READ | libc::PROT_WRITE,
+                                             libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE, -1, 0)};
+                assert!(raw != libc::MAP_FAILED, "failed to map an arena of {} slots", size);
+                (raw, Storage::Mmap(bytes))
+            }
+        };
         Allocator {
-            buf: unsafe {libc::calloc(size as libc::size_t, mem::size_of::<T>() as libc::size_t) as *mut T},
+            buf: ((raw as usize + align - 1) & !(align - 1)) as *mut T,
+            raw: raw,
+            storage: storage,
             capacity: size,
-            n: AtomicUsize::new(0)
+            n: AtomicUsize::new(0),
+            chunk: chunk,
+            buffers: buffers,
+            free: AtomicU64::new(NIL),
+            nfree: AtomicUsize::new(0),
+            nretired: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn usage(&self) -> Usage {
+        let high_water = cmp::min(self.n.load(Ordering::Relaxed), self.capacity);
+        //slots taken from the arena that hold no value
+        let mut unused = self.nfree.load(Ordering::Relaxed);
+        for buffer in self.buffers.iter() {
+            let cursor = buffer.cursor.load(Ordering::Relaxed);
+            if cursor != REFILLING {
+                unused += ((cursor >> 32) as usize).saturating_sub((cursor & NIL) as usize);
+            }
+        }
+        let size = mem::size_of::<T>();
+        let touched = high_water * size + (self.buf as usize - self.raw as usize);
+        Usage {
+            in_use: high_water.saturating_sub(unused),
+            retired: self.nretired.load(Ordering::Relaxed),
+            high_water: high_water,
+            reserved_bytes: self.capacity * size,
+            touched_bytes: cmp::min((touched + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE, self.capacity * size),
         }
     }
 
+    //for_each_live: call f on every value in the arena, retired ones included
+    //unsafe: nothing may alloc, dealloc or reclaim while it runs
+    pub unsafe fn for_each_live<F: FnMut(&T)>(&self, mut f: F) {
+        let live = self.live_slots();
+        for i in 0..live.len() {
+            if live[i] {
+                f(&*self.buf.offset(i as isize));
+            }
+        }
+    }
+
+    //live_slots: which slots below the high-water mark hold a value
+    //every slot taken from the arena was handed out, except the ones still sitting in a thread
+    //buffer and the ones on the free list
+    fn live_slots(&self) -> Vec<bool> {
+        let mut live = vec![true; cmp::min(self.n.load(Ordering::Relaxed), self.capacity)];
+        for buffer in self.buffers.iter() {
+            let cursor = buffer.cursor.load(Ordering::Relaxed);
+            for i in (cursor & NIL) as usize..(cursor >> 32) as usize {
+                live[i] = false;
+            }
+        }
+        let mut i = self.free.load(Ordering::Relaxed) & NIL;
+        while i != NIL {
+            live[i as usize] = false;
+            i = unsafe {ptr::read_unaligned(self.buf.offset(i as isize) as *const u32)} as u64;
+        }
+        live
+    }
+
+    //alloc: like try_alloc, but panics when the arena is full
     pub fn alloc(&self, obj: T) -> &mut T {
-        let i = self.n.fetch_add(1, Ordering::Relaxed);
-        assert!(i < self.capacity);
-        unsafe {*self.buf.offset(i as isize) = obj;}
-        unsafe {&mut *self.buf.offset(i as isize)}
+        self.try_alloc(obj).expect("arena is full")
+    }
+
+    pub fn try_alloc(&self, obj: T) -> Result<&mut T, AllocError> {
+        if let Some(i) = self.pop(&self.free) {
+            //a freed slot holds no value, so nothing must be dropped
+            unsafe {ptr::write(self.buf.offset(i as isize), obj);}
+            return Ok(unsafe {&mut *self.buf.offset(i as isize)});
+        }
+        let i = self.next_slot();
+        if i >= self.capacity {
+            return Err(AllocError::CapacityExceeded);
+        }
+        //the slot is uninitialized, assigning to it would drop whatever garbage it holds
+        unsafe {ptr::write(self.buf.offset(i as isize), obj);}
+        Ok(unsafe {&mut *self.buf.offset(i as isize)})
+    }
+
+    //next_slot: take a slot from the calling thread's chunk, reserving a new chunk when it runs out
+    //the slots of a chunk stay contiguous, so the nodes a thread allocates are still packed together
+    fn next_slot(&self) -> usize {
+        let buffer = self.thread_buffer();
+        loop {
+            let cursor = buffer.cursor.load(Ordering::Relaxed);
+            if cursor == REFILLING {
+                //another thread sharing this buffer is refilling it
+                break;
+            }
+            let next = cursor & 0xffff_ffff;
+            let end = cursor >> 32;
+            if next < end {
+                if buffer.cursor.compare_and_swap(cursor, cursor + 1, Ordering::Relaxed) == cursor {
+                    return next as usize;
+                }
+            } else if buffer.cursor.compare_and_swap(cursor, REFILLING, Ordering::Relaxed) == cursor {
+                let start = self.n.fetch_add(self.chunk, Ordering::Relaxed);
+                if start >= self.capacity {
+                    //arena is full, leave the buffer empty
+                    buffer.cursor.store(((self.capacity as u64) << 32) | self.capacity as u64, Ordering::Relaxed);
+                } else {
+                    let end = if start + self.chunk < self.capacity { start + self.chunk } else { self.capacity };
+                    buffer.cursor.store(((end as u64) << 32) | (start + 1) as u64, Ordering::Relaxed);
+                }
+                return start;
+            }
+        }
+        self.n.fetch_add(1, Ordering::Relaxed)
+    }
+
+    //region: reserve len contiguous slots, bypassing the thread buffers and the free list
+    //returns None if the arena doesn't have len slots left
+    pub fn region(&self, len: usize) -> Option<Region<T>> {
+        loop {
+            let start = self.n.load(Ordering::Relaxed);
+            if start + len > self.capacity {
+                return None;
+            }
+            if self.n.compare_and_swap(start, start + len, Ordering::Relaxed) == start {
+                return Some(Region { mem: self, next: start, end: start + len });
+            }
+        }
+    }
+
+    fn thread_buffer(&self) -> &ThreadBuffer {
+        &self.buffers[THREAD_ID.with(|id| *id) % self.buffers.len()]
+    }
+
+    //dealloc: drop obj and make its slot available to alloc right away
+    //obj must not be reachable by anyone anymore, e.g. a node that was never published
+    pub fn dealloc(&self, obj: &mut T) {
+        let i = self.index_of(obj);
+        unsafe {ptr::drop_in_place(obj as *mut T);}
+        self.push(&self.free, i);
+    }
+
+    //retire: obj has been unlinked, but concurrent readers may still hold a reference to it
+    //the slot is only recycled by the next reclaim
+    pub fn retire(&self, obj: &mut T) {
+        let retired = &self.thread_buffer().retired;
+        let entry = Box::into_raw(Box::new(Retired { slot: self.index_of(obj), next: ptr::null_mut() }));
+        loop {
+            let head = retired.load(Ordering::Relaxed);
+            unsafe {(*entry).next = head;}
+            if retired.compare_and_swap(head, entry, Ordering::Release) == head {
+                break;
+            }
+        }
+        self.nretired.fetch_add(1, Ordering::Relaxed);
+    }
+
+    //reclaim: drop every retired object and move its slot to the free list
+    //taking &mut self proves that no reader can still be holding one of them
+    pub fn reclaim(&mut self) {
+        if *self.nretired.get_mut() == 0 {
+            return;
+        }
+        for i in self.take_retired() {
+            unsafe {ptr::drop_in_place(self.buf.offset(i as isize));}
+            self.push(&self.free, i);
+        }
+    }
+
+    //take_retired: empty the retired lists, returning the slots they held
+    fn take_retired(&mut self) -> Vec<usize> {
+        *self.nretired.get_mut() = 0;
+        let mut slots = Vec::new();
+        for b in 0..self.buffers.len() {
+            let mut entry = mem::replace(self.buffers[b].retired.get_mut(), ptr::null_mut());
+            while !entry.is_null() {
+                let retired = unsafe {Box::from_raw(entry)};
+                slots.push(retired.slot);
+                entry = retired.next;
+            }
+        }
+        slots
+    }
+
+    fn index_of(&self, obj: &T) -> usize {
+        let i = (obj as *const T as usize - self.buf as usize) / mem::size_of::<T>();
+        debug_assert!(i < self.capacity);
+        i
+    }
+
+    //push: put slot i on top of stack
+    fn push(&self, stack: &AtomicU64, i: usize) {
+        let link = unsafe {self.buf.offset(i as isize) as *mut u32};
+        loop {
+            let head = stack.load(Ordering::Acquire);
+            unsafe {ptr::write_unaligned(link, (head & NIL) as u32);}
+            let new = (((head >> 32) + 1) << 32) | i as u64;
+            if stack.compare_and_swap(head, new, Ordering::AcqRel) == head {
+                self.nfree.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+
+    //pop: take the slot on top of stack, if any
+    fn pop(&self, stack: &AtomicU64) -> Option<usize> {
+        loop {
+            let head = stack.load(Ordering::Acquire);
+            let i = head & NIL;
+            if i == NIL {
+                return None;
+            }
+            //if the slot was popped and reused meanwhile, the link is garbage but the tag changed
+            let link = unsafe {ptr::read_volatile(self.buf.offset(i as isize) as *const u32)};
+            let new = (((head >> 32) + 1) << 32) | link as u64;
+            if stack.compare_and_swap(head, new, Ordering::AcqRel) == head {
+                self.nfree.fetch_sub(1, Ordering::Relaxed);
+                return Some(i as usize);
+            }
+        }
+    }
+}
+
+//run of contiguous slots reserved by Allocator::region, handed out in order
+//slots that were not handed out go back to the free list when the region is dropped
+pub struct Region<'a, T: 'a> {
+    mem: &'a Allocator<T>,
+    next: usize,
+    end: usize,
+}
+
+impl<'a, T> Region<'a, T> {
+    pub fn alloc(&mut self, obj: T) -> &'a mut T {
+        assert!(self.next < self.end);
+        let i = self.next;
+        self.next += 1;
+        unsafe {ptr::write(self.mem.buf.offset(i as isize), obj);}
+        unsafe {&mut *self.mem.buf.offset(i as isize)}
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.end - self.next
+    }
+}
+
+impl<'a, T> Drop for Region<'a, T> {
+    fn drop(&mut self) {
+        for i in self.next..self.end {
+            self.mem.push(&self.mem.free, i);
+        }
+    }
+}
+
+impl<T> Drop for Allocator<T> {
+    //drop the values still stored in the arena, then give the arena back
+    fn drop(&mut self) {
+        //retired slots are not on the free list, so their values are dropped with the live ones
+        self.take_retired();
+        if mem::needs_drop::<T>() {
+            let live = self.live_slots();
+            for i in 0..live.len() {
+                if live[i] {
+                    unsafe {ptr::drop_in_place(self.buf.offset(i as isize));}
+                }
+            }
+        }
+        match self.storage {
+            Storage::Calloc => unsafe {libc::free(self.raw);},
+            Storage::Mmap(bytes) => unsafe {libc::munmap(self.raw, bytes as libc::size_t);},
+        }
+    }
+}
+
+//backend that holds the nodes of a LockfreeTrie
+//alloc, dealloc, retire and reclaim behave like the Allocator methods of the same name
+pub trait NodeAllocator<T> {
+    fn try_alloc(&self, obj: T) -> Result<&mut T, AllocError>;
+    fn dealloc(&self, obj: &mut T);
+    fn retire(&self, obj: &mut T);
+    fn reclaim(&mut self);
+
+    fn alloc(&self, obj: T) -> &mut T {
+        self.try_alloc(obj).expect("allocator is full")
+    }
+
+    //region: len contiguous slots, if the backend is an arena
+    fn region(&self, len: usize) -> Option<Region<T>> {
+        None
+    }
+
+    //validate: called on the nodes a lookup or insert visits, to check they are still live
+    fn validate(&self, obj: &T) {}
+
+    fn usage(&self) -> Usage;
+
+    //for_each_live: call f on every value the backend holds, retired ones included
+    //unsafe: nothing may alloc, dealloc or reclaim while it runs
+    unsafe fn for_each_live<F: FnMut(&T)>(&self, f: F);
+}
+
+impl<T> NodeAllocator<T> for Allocator<T> {
+    fn try_alloc(&self, obj: T) -> Result<&mut T, AllocError> { Allocator::try_alloc(self, obj) }
+    fn dealloc(&self, obj: &mut T) { Allocator::dealloc(self, obj) }
+    fn retire(&self, obj: &mut T) { Allocator::retire(self, obj) }
+    fn reclaim(&mut self) { Allocator::reclaim(self) }
+    fn region(&self, len: usize) -> Option<Region<T>> { Allocator::region(self, len) }
+    fn usage(&self) -> Usage { Allocator::usage(self) }
+    unsafe fn for_each_live<F: FnMut(&T)>(&self, f: F) { Allocator::for_each_live(self, f) }
+}
+
+//every node in its own Box from the global allocator, as a baseline without any packing
+//unlike Allocator it is not lock-free: alloc, dealloc and retire each take a lock to keep
+//track of the boxes
+pub struct BoxAllocator<T> {
+    live: Mutex<HashSet<usize>>, //addresses of the boxes not freed yet
+    retired: Mutex<Vec<usize>>,
+    high_water: AtomicUsize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> BoxAllocator<T> {
+    pub fn new() -> Self {
+        BoxAllocator {
+            live: Mutex::new(HashSet::new()),
+            retired: Mutex::new(Vec::new()),
+            high_water: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> NodeAllocator<T> for BoxAllocator<T> {
+    //the global allocator aborts instead of failing, so this never returns an error
+    fn try_alloc(&self, obj: T) -> Result<&mut T, AllocError> {
+        let obj = Box::into_raw(Box::new(obj));
+        let mut live = self.live.lock().unwrap();
+        live.insert(obj as usize);
+        if live.len() > self.high_water.load(Ordering::Relaxed) {
+            self.high_water.store(live.len(), Ordering::Relaxed);
+        }
+        Ok(unsafe {&mut *obj})
+    }
+
+    fn dealloc(&self, obj: &mut T) {
+        let obj = obj as *mut T;
+        assert!(self.live.lock().unwrap().remove(&(obj as usize)));
+        unsafe {Box::from_raw(obj);}
+    }
+
+    fn retire(&self, obj: &mut T) {
+        self.retired.lock().unwrap().push(obj as *mut T as usize);
+    }
+
+    fn reclaim(&mut self) {
+        let live = self.live.get_mut().unwrap();
+        for obj in self.retired.get_mut().unwrap().drain(..) {
+            assert!(live.remove(&obj));
+            unsafe {Box::from_raw(obj as *mut T);}
+        }
+    }
+
+    fn usage(&self) -> Usage {
+        let in_use = self.live.lock().unwrap().len();
+        Usage {
+            in_use: in_use,
+            retired: self.retired.lock().unwrap().len(),
+            high_water: self.high_water.load(Ordering::Relaxed),
+            reserved_bytes: in_use * mem::size_of::<T>(),
+            touched_bytes: in_use * mem::size_of::<T>(),
+        }
+    }
+
+    unsafe fn for_each_live<F: FnMut(&T)>(&self, mut f: F) {
+        for &obj in self.live.lock().unwrap().iter() {
+            f(&*(obj as *const T));
+        }
+    }
+}
+
+impl<T> Drop for BoxAllocator<T> {
+    fn drop(&mut self) {
+        for &obj in self.live.get_mut().unwrap().iter() {
+            unsafe {Box::from_raw(obj as *mut T);}
+        }
+    }
+}
+
+//Allocator backed by an anonymous mapping instead of calloc
+//MAP_NORESERVE lets the arena be much larger than what the trie ends up using, and the
+//mapping is marked for transparent huge pages to cut TLB misses on a large trie
+pub struct MmapAllocator<T>(Allocator<T>);
+
+impl<T> MmapAllocator<T> {
+    pub fn new(size: usize) -> Self {
+        let arena = Allocator::reserve(size);
+        if let Storage::Mmap(bytes) = arena.storage {
+            //best effort: transparent huge pages may be disabled
+            #[cfg(target_os = "linux")]
+            unsafe {libc::madvise(arena.raw, bytes as libc::size_t, libc::MADV_HUGEPAGE);}
+        }
+        MmapAllocator(arena)
+    }
+}
+
+impl<T> NodeAllocator<T> for MmapAllocator<T> {
+    fn try_alloc(&self, obj: T) -> Result<&mut T, AllocError> { self.0.try_alloc(obj) }
+    fn dealloc(&self, obj: &mut T) { self.0.dealloc(obj) }
+    fn retire(&self, obj: &mut T) { self.0.retire(obj) }
+    fn reclaim(&mut self) { self.0.reclaim() }
+    fn region(&self, len: usize) -> Option<Region<T>> { self.0.region(len) }
+    fn usage(&self) -> Usage { self.0.usage() }
+    unsafe fn for_each_live<F: FnMut(&T)>(&self, f: F) { self.0.for_each_live(f) }
+}
+
+//Allocator that never reuses a slot and overwrites freed ones with POISON, so that tests can
+//catch a node being used after it was freed: validate panics on a poisoned node
+pub struct DebugAllocator<T> {
+    arena: Allocator<T>,
+    quarantine: Mutex<Vec<usize>>, //freed slots, poisoned and never handed out again
+}
+
+impl<T> DebugAllocator<T> {
+    pub fn new(size: usize) -> Self {
+        DebugAllocator {
+            arena: Allocator::new(size),
+            quarantine: Mutex::new(Vec::new()),
+        }
+    }
+
+    //is_poisoned: true if obj was freed
+    pub fn is_poisoned(&self, obj: &T) -> bool {
+        let bytes = obj as *const T as *const u8;
+        (0..mem::size_of::<T>()).all(|b| unsafe {*bytes.offset(b as isize)} == POISON)
+    }
+
+    fn poison(&self, i: usize) {
+        unsafe {ptr::write_bytes(self.arena.buf.offset(i as isize) as *mut u8, POISON, mem::size_of::<T>());}
+        self.quarantine.lock().unwrap().push(i);
+    }
+}
+
+impl<T> NodeAllocator<T> for DebugAllocator<T> {
+    fn try_alloc(&self, obj: T) -> Result<&mut T, AllocError> {
+        self.arena.try_alloc(obj)
+    }
+
+    fn dealloc(&self, obj: &mut T) {
+        assert!(!self.is_poisoned(obj), "double free of slot {}", self.arena.index_of(obj));
+        let i = self.arena.index_of(obj);
+        unsafe {ptr::drop_in_place(obj as *mut T);}
+        self.poison(i);
+    }
+
+    fn retire(&self, obj: &mut T) {
+        assert!(!self.is_poisoned(obj), "retiring freed slot {}", self.arena.index_of(obj));
+        self.arena.retire(obj)
+    }
+
+    fn reclaim(&mut self) {
+        for i in self.arena.take_retired() {
+            unsafe {ptr::drop_in_place(self.arena.buf.offset(i as isize));}
+            self.poison(i);
+        }
+    }
+
+    fn region(&self, len: usize) -> Option<Region<T>> {
+        self.arena.region(len)
+    }
+
+    fn validate(&self, obj: &T) {
+        assert!(!self.is_poisoned(obj), "use after free of slot {}", self.arena.index_of(obj));
+    }
+
+    //quarantined slots are never handed out again, so they count as touched but not in use
+    fn usage(&self) -> Usage {
+        let mut usage = self.arena.usage();
+        usage.in_use -= self.quarantine.lock().unwrap().len();
+        usage
+    }
+
+    unsafe fn for_each_live<F: FnMut(&T)>(&self, mut f: F) {
+        let mut live = self.arena.live_slots();
+        for &i in self.quarantine.lock().unwrap().iter() {
+            live[i] = false;
+        }
+        for i in 0..live.len() {
+            if live[i] {
+                f(&*self.arena.buf.offset(i as isize));
+            }
+        }
+    }
+}
+
+impl<T> Drop for DebugAllocator<T> {
+    fn drop(&mut self) {
+        //the quarantined slots hold no value; on the free list the arena won't drop them again
+        for &i in self.quarantine.get_mut().unwrap().iter() {
+            self.arena.push(&self.arena.free, i);
+        }
     }
 }
@@ -0,0 +1,251 @@
+/// Concurrent Cache Conscious Trie with atomic slots
+/// The lock-free counterpart of MutexContiguousTrie and RwContiguousTrie: the same contiguous
+/// blocks as ContiguousTrie, but every slot is an AtomicU64, so the trie is shared between
+/// threads behind an Arc and none of its operations take a lock.
+///
+/// All the slots the trie may use are allocated zeroed when it is created, so memory never moves.
+/// A missing block is reserved from them by bumping a cursor and linked into its parent slot with
+/// a CAS; the thread that loses the CAS follows the winner's block instead, and the block it
+/// reserved stays unused. get is wait-free: one atomic load per level.
+
+// Slot encoding:
+//
+//     0                       nothing
+//     start                   interior slot, start of the block below (never 0, the root is there)
+//     LEAF | packed value     leaf slot holding a value
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::marker::PhantomData;
+use std::mem;
+use cchamt::{PrimInt, TrieData};
+use error::TrieError;
+
+const EMPTY: u64 = 0;
+// set in the leaf slots that hold a value, the value is in the low 32 bits
+const LEAF: u64 = 1 << 32;
+
+/// Values that fit in the low 32 bits of a slot
+pub trait PackedValue: TrieData {
+    fn pack(self) -> u32;
+    fn unpack(bits: u32) -> Self;
+}//trait PackedValue
+
+macro_rules! packed_value {
+    ($($t:ty => $u:ty),*) => {$(
+        impl PackedValue for $t {
+            #[inline(always)]
+            fn pack(self) -> u32 { self as $u as u32 }
+            #[inline(always)]
+            fn unpack(bits: u32) -> Self { bits as $u as $t }
+        }
+    )*};
+}//packed_value
+
+packed_value!(u8 => u8, u16 => u16, u32 => u32, i8 => u8, i16 => u16, i32 => u32);
+
+/// Core Data structure
+#[derive(Debug)]
+pub struct AtomicContiguousTrie<K: PrimInt, V: PackedValue> {
+    memory: Box<[AtomicU64]>,
+    next: AtomicUsize, // start of the next block to hand out
+    len: AtomicUsize, // number of keys in the trie
+    key_length: usize,
+    segments: Vec<usize>, // the size of the key segment consumed at each depth
+    shifts: Vec<usize>, // the shift that brings the segment of each depth to the low bits
+    _key: PhantomData<K>,
+    _value: PhantomData<V>,
+}//struct AtomicContiguousTrie
+
+impl<K: PrimInt, V: PackedValue> AtomicContiguousTrie<K, V> {
+    //constructor
+    // key_length: number of low bits of K that make up the key
+    // key_segment_size: length of a key segment (a key_group)
+    // capacity: number of slots to allocate, the blocks of the trie can never take more than that
+    pub fn new(key_length: usize, key_segment_size: usize, capacity: usize) -> Self {
+        // key_length needs to be multiple of key_segment_size
+        assert_eq!(key_length % key_segment_size, 0);
+        Self::with_segments(&vec![key_segment_size; key_length / key_segment_size], capacity)
+    }//constructor
+
+    //constructor with a segment size per depth, from the root down, see ContiguousTrie::with_segments
+    pub fn with_segments(segments: &[usize], capacity: usize) -> Self {
+        assert!(!segments.is_empty());
+        assert!(segments.iter().all(|&size| size > 0 && size < 64));
+        let key_length: usize = segments.iter().sum();
+        // the key has to fit in the key type
+        assert!(key_length <= K::BITS);
+        // the root block is the first block of memory
+        assert!(capacity >= 1 << segments[0], "capacity is smaller than the root block");
+
+        let mut shifts = Vec::with_capacity(segments.len());
+        let mut consumed = 0;
+        for &size in segments {
+            consumed += size;
+            shifts.push(key_length - consumed);
+        }//for
+
+        // zeroed memory comes straight from the kernel, so the pages are only backed once a block
+        // is handed out in them, and AtomicU64 has the same layout as u64
+        let mut zeroed = vec![0u64; capacity];
+        let memory = unsafe {
+            let memory = Vec::from_raw_parts(zeroed.as_mut_ptr() as *mut AtomicU64, zeroed.len(), zeroed.capacity());
+            mem::forget(zeroed);
+            memory.into_boxed_slice()
+        };
+
+        AtomicContiguousTrie {
+            memory,
+            next: AtomicUsize::new(1 << segments[0]),
+            len: AtomicUsize::new(0),
+            key_length,
+            segments: segments.to_vec(),
+            shifts,
+            _key: PhantomData,
+            _value: PhantomData,
+        }
+    }//with_segments
+
+    // number of slots in a block at the given depth
+    #[inline(always)]
+    fn array_length(&self, depth: usize) -> usize {
+        1 << self.segments[depth]
+    }//array_length
+
+    // the number of keys in the trie
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }//len
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }//is_empty
+
+    // the number of slots handed out to blocks so far
+    pub fn allocated_slots(&self) -> usize {
+        let next = self.next.load(Ordering::Relaxed);
+        if next < self.memory.len() { next } else { self.memory.len() }
+    }//allocated_slots
+
+    // the number of slots allocated when the trie was created
+    pub fn capacity(&self) -> usize {
+        self.memory.len()
+    }//capacity
+
+    #[inline(always)]
+    fn compute_index(&self, key: u64, depth: usize) -> usize {
+        ((key >> self.shifts[depth]) & ((1 << self.segments[depth]) - 1)) as usize
+    }//compute_index
+
+    // reject keys with bits set above key_length
+    #[inline(always)]
+    fn check(&self, key: K) -> Result<(), TrieError> {
+        if self.key_length < 64 && key.to_u64() >> self.key_length != 0 {
+            return Err(TrieError::OutOfRange);
+        }//if
+        Ok(())
+    }//check
+
+    // return the index of the leaf slot, or None if a block on the way hasn't been linked yet
+    #[inline(always)]
+    fn key2index(&self, key: K) -> Option<usize> {
+        let key = key.to_u64();
+        let mut current_index = self.compute_index(key, 0);
+        for depth in 1..self.segments.len() {
+            let child = self.memory[current_index].load(Ordering::Acquire);
+            if child == EMPTY {
+                return None;
+            }//if
+            current_index = child as usize + self.compute_index(key, depth);
+        }//for
+        Some(current_index)
+    }//key2index
+
+    // same as key2index, linking the missing blocks on the way
+    fn key2index_mut(&self, key: K) -> Result<usize, TrieError> {
+        let key = key.to_u64();
+        let mut current_index = self.compute_index(key, 0);
+        for depth in 1..self.segments.len() {
+            let mut child = self.memory[current_index].load(Ordering::Acquire);
+            if child == EMPTY {
+                let length = self.array_length(depth);
+                let start = self.next.fetch_add(length, Ordering::Relaxed);
+                if start + length > self.memory.len() {
+                    return Err(TrieError::Full);
+                }//if
+                // the block is still zeroed, so publishing its start is enough
+                child = match self.memory[current_index].compare_exchange(EMPTY, start as u64,
+                                                                          Ordering::AcqRel, Ordering::Acquire) {
+                    Ok(_) => start as u64,
+                    Err(winner) => winner, // another thread linked a block first
+                };//match
+            }//if
+            current_index = child as usize + self.compute_index(key, depth);
+        }//for
+        Ok(current_index)
+    }//key2index_mut
+
+    //insert the value into the Trie
+    //return the value that was stored under the key before, if any
+    pub fn insert(&self, value: V, key: K) -> Result<Option<V>, TrieError> {
+        self.check(key)?;
+        let index = self.key2index_mut(key)?;
+        let old = self.memory[index].swap(LEAF | value.pack() as u64, Ordering::AcqRel);
+        if old == EMPTY {
+            self.len.fetch_add(1, Ordering::Relaxed);
+            return Ok(None);
+        }//if
+        Ok(Some(V::unpack(old as u32)))
+    }//insert
+
+    //insert the value only if the key isn't in the Trie yet
+    pub fn try_insert(&self, value: V, key: K) -> Result<(), TrieError> {
+        self.check(key)?;
+        let index = self.key2index_mut(key)?;
+        match self.memory[index].compare_exchange(EMPTY, LEAF | value.pack() as u64, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => {
+                self.len.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(_) => Err(TrieError::Duplicate),
+        }//match
+    }//try_insert
+
+    //return true if the key entry exists
+    #[inline(always)]
+    pub fn contain(&self, key: K) -> bool {
+        match self.get(key) {
+            Ok(found) => found.is_some(),
+            Err(_) => false, // a key that doesn't fit can't be in the trie
+        }//match
+    }//contain
+
+    // return the value in the given key and wrap it with an Option, or an error for a key that doesn't fit
+    #[inline(always)]
+    pub fn get(&self, key: K) -> Result<Option<V>, TrieError> {
+        self.check(key)?;
+        match self.key2index(key) {
+            Some(index) => {
+                let slot = self.memory[index].load(Ordering::Acquire);
+                Ok(if slot == EMPTY { None } else { Some(V::unpack(slot as u32)) })
+            }
+            None => Ok(None), // can't return anything if the key's block was never linked
+        }//match
+    }//get
+
+    // remove the entry and return its value
+    // the blocks on its path stay linked
+    pub fn remove(&self, key: K) -> Result<Option<V>, TrieError> {
+        self.check(key)?;
+        match self.key2index(key) {
+            Some(index) => {
+                let old = self.memory[index].swap(EMPTY, Ordering::AcqRel);
+                if old == EMPTY {
+                    return Ok(None);
+                }//if
+                self.len.fetch_sub(1, Ordering::Relaxed);
+                Ok(Some(V::unpack(old as u32)))
+            }
+            None => Ok(None), // the key's block was never linked
+        }//match
+    }//remove
+}//impl AtomicContiguousTrie
@@ -0,0 +1,226 @@
+/// Cache Conscious Trie over byte strings
+/// Same contiguous block layout as `src/cchamt.rs`, but every level consumes one byte of the key,
+/// so keys can be any `&[u8]` or `&str` of any length instead of fixed-width integers.
+///
+/// Every block has 257 slots: slot 0 is the terminal slot, holding the value of the key that ends
+/// at this block, and slot b + 1 leads to the block of the keys continuing with byte b.
+/// Walking the slots of a block in order therefore visits the keys in lexicographic order.
+
+// For instances, after inserting "a" and "ab":
+//
+//     root block:  [0: -] [b'a' + 1: -> A] ...
+//     A:           [0: value of "a"] [b'b' + 1: -> B] ...
+//     B:           [0: value of "ab"] ...
+
+use std::mem;
+use error::TrieError;
+
+pub trait TrieData: Clone + Copy + Eq + PartialEq {}
+
+impl<T> TrieData for T where T: Clone + Copy + Eq + PartialEq {}
+
+// slots in a block, one per byte value plus the terminal slot
+const ARRAY_LENGTH: usize = 257;
+// slot holding the value of the key that ends at the block
+const TERMINAL: usize = 0;
+
+/// Core Data structure
+#[derive(Debug)]
+pub struct ByteContiguousTrie<V: TrieData> {
+    memory: Vec<Option<SubTrie<V>>>,
+    len: usize, // number of keys in the trie
+}//struct ByteContiguousTrie
+
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SubTrie<V: TrieData> {
+    pub data: Option<V>,
+    depth: usize,
+    children_offset: Option<usize>,    // the start position in memory of the block below this slot
+}//struct SubTrie
+
+impl<V: TrieData> ByteContiguousTrie<V> {
+    //constructor
+    pub fn new() -> Self {
+        let mut trie = ByteContiguousTrie {
+            memory: Vec::new(),
+            len: 0,
+        };
+        trie.alloc_block(); // the root block
+        trie
+    }//constructor
+
+    // push an empty block onto the end of memory and return its start
+    fn alloc_block(&mut self) -> usize {
+        let start = self.memory.len();
+        self.memory.resize(start + ARRAY_LENGTH, None);
+        start
+    }//alloc_block
+
+    // the number of keys in the trie
+    pub fn len(&self) -> usize {
+        self.len
+    }//len
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }//is_empty
+
+    // the number of slots allocated so far
+    pub fn allocated_slots(&self) -> usize {
+        self.memory.len()
+    }//allocated_slots
+
+    // return the index of the terminal slot of the key, or None if a block on the way hasn't been allocated
+    #[inline(always)]
+    fn key2index(&self, key: &[u8]) -> Option<usize> {
+        let mut block = 0;
+        for &byte in key {
+            match &self.memory[block + byte as usize + 1] {
+                Some(SubTrie { children_offset: Some(b), .. }) => block = *b,
+                _ => return None,
+            }//match
+        }//for
+        Some(block + TERMINAL)
+    }//key2index
+
+    // same as key2index, allocating the missing blocks on the way
+    fn key2index_mut(&mut self, key: &[u8]) -> usize {
+        let mut block = 0;
+        for (depth, &byte) in key.iter().enumerate() {
+            let index = block + byte as usize + 1;
+            block = match &self.memory[index] {
+                Some(SubTrie { children_offset: Some(b), .. }) => *b,
+                _ => {
+                    let b = self.alloc_block();
+                    self.memory[index] = Some(SubTrie {
+                        data: None,
+                        depth,
+                        children_offset: Some(b),
+                    });
+                    b
+                }
+            };//match &self.memory[index]
+        }//for
+        block + TERMINAL
+    }//key2index_mut
+
+    // insert the entry to the trie
+    // return the value that was stored under the key before, if any
+    pub fn insert<K: AsRef<[u8]>>(&mut self, value: V, key: K) -> Option<V> {
+        let key = key.as_ref();
+        let index = self.key2index_mut(key);
+        let leaf = Some(SubTrie {
+            data: Some(value),
+            depth: key.len(),
+            children_offset: None,
+        });
+        let old = mem::replace(&mut self.memory[index], leaf).and_then(|old| old.data);
+        if old.is_none() {
+            self.len += 1;
+        }//if
+        old
+    }//insert
+
+    // insert the entry only if the key isn't in the trie yet
+    pub fn try_insert<K: AsRef<[u8]>>(&mut self, value: V, key: K) -> Result<(), TrieError> {
+        if self.contain(key.as_ref()) {
+            return Err(TrieError::Duplicate);
+        }//if
+        self.insert(value, key);
+        Ok(())
+    }//try_insert
+
+    // return true if the key entry exists
+    #[inline(always)]
+    pub fn contain<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }//contain
+
+    // return the value in the given key and wrap it with an Option
+    #[inline(always)]
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<V> {
+        match self.key2index(key.as_ref()) {
+            Some(index) => self.memory[index].as_ref().and_then(|leaf| leaf.data),
+            None => None, // can't return anything if the key's block was never allocated
+        }//match
+    }//get
+
+    // remove the entry and return its value
+    // the blocks on its path stay allocated
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) -> Option<V> {
+        let old = match self.key2index(key.as_ref()) {
+            Some(index) => mem::replace(&mut self.memory[index], None).and_then(|old| old.data),
+            None => None,
+        };//match
+        if old.is_some() {
+            self.len -= 1;
+        }//if
+        old
+    }//remove
+
+    // iterate over the (key, value) pairs in lexicographic key order
+    pub fn iter(&self) -> ByteIter<V> {
+        ByteIter { trie: self, stack: vec![(0, 0)], key: Vec::new() }
+    }//iter
+
+    // iterate over the (key, value) pairs whose key starts with prefix, in lexicographic key order
+    pub fn prefix<K: AsRef<[u8]>>(&self, prefix: K) -> ByteIter<V> {
+        let prefix = prefix.as_ref();
+        match self.key2index(prefix) {
+            Some(index) => ByteIter { trie: self, stack: vec![(index - TERMINAL, 0)], key: prefix.to_vec() },
+            None => ByteIter { trie: self, stack: Vec::new(), key: Vec::new() },
+        }//match
+    }//prefix
+}//impl ByteContiguousTrie
+
+// iterator over the (key, value) pairs of a ByteContiguousTrie in key order
+// a depth first walk: the stack holds the start of each block on the way down and
+// the next slot to visit in it, key holds the bytes leading to the top block
+pub struct ByteIter<'a, V: TrieData + 'a> {
+    trie: &'a ByteContiguousTrie<V>,
+    stack: Vec<(usize, usize)>,
+    key: Vec<u8>,
+}//struct ByteIter
+
+impl<'a, V: TrieData> Iterator for ByteIter<'a, V> {
+    type Item = (Vec<u8>, V);
+
+    fn next(&mut self) -> Option<(Vec<u8>, V)> {
+        loop {
+            let (block, slot) = match self.stack.last_mut() {
+                Some(top) => {
+                    let current = *top;
+                    top.1 += 1;
+                    current
+                }
+                None => return None,
+            };//match
+            if slot == ARRAY_LENGTH {
+                // done with this block, go back up to its parent
+                self.stack.pop();
+                if !self.stack.is_empty() {
+                    self.key.pop();
+                }//if
+                continue;
+            }//if
+            match &self.trie.memory[block + slot] {
+                Some(SubTrie { children_offset: Some(b), .. }) => {
+                    self.key.push((slot - 1) as u8);
+                    self.stack.push((*b, 0));
+                }//Some(b)
+                Some(SubTrie { data: Some(value), .. }) => return Some((self.key.clone(), *value)),
+                _ => {}
+            }//match
+        }//loop
+    }//next
+}//impl Iterator for ByteIter
+
+impl<'a, V: TrieData> IntoIterator for &'a ByteContiguousTrie<V> {
+    type Item = (Vec<u8>, V);
+    type IntoIter = ByteIter<'a, V>;
+
+    fn into_iter(self) -> ByteIter<'a, V> {
+        self.iter()
+    }//into_iter
+}//impl IntoIterator for &ByteContiguousTrie
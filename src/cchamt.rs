@@ -21,66 +21,105 @@
 //
 //  first, exit if key_length is not a multiple of key_segment_size
 //         32 % 8 = 0, so we continue
-//  next, declare memory as a Vec<Option<SubTrie<T>>>
-//  initialize node_length = 0 (this will be the memory size)
-//  use array_length and multitude to compute node_length
-//  start with multitude = array_length = 2^(key_segment_size) = 2^8
-//  in the for loop, exclusively go from 0 to (key_length/key_segment_size - 1) = (32/8 - 1) = 3
-//  after each iteration we have:
+//  next, declare one sparse directory of blocks per level, a BTreeMap from offset to block
+//  the trie is made of blocks of array_length = 2^(key_segment_size) = 2^8 slots,
+//  one block per interior node, each stored contiguously
+//  a level is numbered as if all of its blocks existed, as MutexContiguousTrie lays it out: the
+//  children of slot i start at children_offset = (i + 1) * array_length in MutexContiguousTrie,
+//  where the levels are numbered one after the other, which is i * array_length counted from the
+//  start of the level below; the leaf of a key k is therefore at offset k of the last level
+//  only the blocks that hold something exist, each at its offset in the directory of its level:
+//  the root block is allocated up front, with 2^8 Option = None entries, and every other block
+//  the first time a key needs it, so memory grows with the populated key space and new(64, 8)
+//  only allocates the root
+//  the directory keeps the blocks of a level in key order, so the leaves of the keys in a range
+//  are a window of offsets, found without walking the interior
 //
-//      node_length += multitude  => 2^8        => 2^8 + 2^16 => 2^8 + 2^16 2^24
-//      multitude *= array_length => 2^16       => 2^24       => 2^32
+// with_segments(segments: &[usize]) -> Self
+//  segments = [16, 8, 8]
 //
-//      (iteration:               0             1             2)
-//
-//  we initialize memory with capacity node_length = 2^8 + 2^16 2^24
-//  finally, we push Option = None onto memory 2^8 + 2^16 2^24 times and return the struct
+//  the same trie with a segment size per depth: key_length = 16 + 8 + 8 = 32,
+//  the root block has 2^16 slots and the blocks below it 2^8 slots each
+//  new(32, 8) is with_segments(&[8, 8, 8, 8])
 //
 //========
 // insert
 //========
 //
-// insert(&mut self, value: T, key: &[u8])
+// insert(&mut self, value: V, key: K)
 //  value = 'apple'
-//  key = [0,0,0,1,1,0,1,0]
-//
-//  first, we initialize index_depth_pair = key2index(key) = (0001, 0)
-//                                                      or = (00011010, 1)
-//  if index_depth_pair.0 >= mem.len() [i.e., if we want to insert outside the current size of our memory]
+//  key = 0b0001_1010 (key_length = 8, key_segment_size = 4)
 //
-//      push_amount = index_depth_pair.0 - mem.len() + 1 (amount to add to memory)
-//      for 0 to push_amount (excusive)
+//  each segment is pulled out of the key with a shift and a mask, most significant first:
+//      segment(depth) = (key >> shift(depth)) & (2^segments[depth] - 1)
+//      where shift(depth) = key_length - (segments[0] + .. + segments[depth])
 //
-//          mem.push(None) (add this many Option = None to the memory Vec)
-
-//  if memory[index_depth_pair.0].is_some() then assert!(false) -- if there is something there,
-//   then we didn't go far enough in the trie, so there is an error of some sort
-//  finally, insert a subtrie with value = 'apple' at index index_depth_pair.0
+//  we start at index = segment(0) = 0001 in the root block
+//  slot 0001 of the root is an interior slot; if it has no children block yet, we allocate
+//   2^4 Option = None at offset 0001 * 2^4 of level 1 and record that offset as its children_offset
+//  the next index is children_offset + segment(1) = 0001 0000 + 1010, the key itself, and we are
+//   at the last segment, so this is the leaf slot
+//  finally, insert a subtrie with value = 'apple' at index leaf, and return the value that
+//   was there before if the key was already inserted
 //
 
 // BEGINNING OF CODE:
 
+use std::fmt::Debug;
+use std::marker::PhantomData;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::mem;
+use std::cmp;
+use std::ops::{Bound, RangeBounds};
+use std::collections::{btree_map, BTreeMap};
+use std::iter::Enumerate;
+use std::slice;
+use std::io;
+use std::path::Path;
+use error::TrieError;
+use mapped_cchamt::{self, Entry, Pod};
 
 pub trait TrieData: Clone + Copy + Eq + PartialEq {}
 
 impl<T> TrieData for T where T: Clone + Copy + Eq + PartialEq {}
 
+/// Fixed-width unsigned integers that can be used as keys of a ContiguousTrie
+pub trait PrimInt: Copy + Eq + Ord + Debug {
+    const BITS: usize;
+
+    fn to_u64(self) -> u64;
+    fn from_u64(v: u64) -> Self;
+}//trait PrimInt
+
+macro_rules! prim_int {
+    ($($t:ty),*) => {$(
+        impl PrimInt for $t {
+            const BITS: usize = ::std::mem::size_of::<$t>() * 8;
+
+            #[inline(always)]
+            fn to_u64(self) -> u64 { self as u64 }
+            #[inline(always)]
+            fn from_u64(v: u64) -> Self { v as $t }
+        }
+    )*};
+}//prim_int
+
+prim_int!(u8, u16, u32, u64, usize);
+
 /// Private Functions for this module
 
-/// compute the depth in the trie using the array index of trie.memory
-// TODO bug here
+/// compute the depth in the trie using the index of a slot when the levels are numbered one after
+/// the other, for a trie whose levels are all populated, level d being 2^(segments[0] + .. + segments[d]) slots
 #[inline(always)]
-fn get_depth(key_group: usize, index: usize) -> usize {
+fn get_depth(segments: &[usize], index: usize) -> usize {
     let mut depth = 0;
-    let key_length = u32::pow(2, key_group as u32); // the number of distinct keys produced by the key group
-    let mut multitude = key_length; // multitudes of key_length
-    let mut compare = multitude; // sum of multitudes
+    let mut multitude = 1usize << segments[0]; // number of slots in the level
+    let mut compare = multitude; // sum of the slots of the levels so far
 
-    while index >= compare as usize {
+    while index >= compare && depth + 1 < segments.len() {
         depth += 1;
-        multitude *= key_length;
+        multitude <<= segments[depth];
         compare += multitude;
     }//while
     depth
@@ -88,159 +127,706 @@ fn get_depth(key_group: usize, index: usize) -> usize {
 
 /// Core Data structure
 #[derive(Debug)]
-pub struct ContiguousTrie<T: TrieData> {
-    memory: Vec<Option<SubTrie<T>>>,
+pub struct ContiguousTrie<K: PrimInt, V: TrieData> {
+    levels: Vec<BTreeMap<u64, Block<V>>>, // the blocks of each depth by offset, see new at the top of this file
     key_length: usize,
-    key_segment_size: usize,
+    segments: Vec<usize>, // the size of the key segment consumed at each depth
+    shifts: Vec<usize>, // the shift that brings the segment of each depth to the low bits
+    _key: PhantomData<K>,
 }//struct ContiguousTrie
 
+// the 2^segments[depth] slots of a block of the given depth
+type Block<V> = Box<[Option<SubTrie<V>>]>;
 
 #[derive(Clone, Eq, PartialEq, Debug)]
-pub struct SubTrie<T: TrieData> {
-    pub data: Option<T>,
+pub struct SubTrie<V: TrieData> {
+    pub data: Option<V>,
     depth: usize,
-    children_offset: Option<usize>,    // the start position in allocator that place the array in hash trie
+    children_offset: Option<u64>,    // the offset of the children block in the level below
 }//struct SubTrie
 
-// Contiguous store all the nodes contiguous with the sequential order of key
-impl<T: TrieData> ContiguousTrie<T> {
+// Contiguous store every block of siblings contiguously, blocks are allocated on demand
+impl<K: PrimInt, V: TrieData> ContiguousTrie<K, V> {
     //constructor
-    // key_length: length of the key
+    // key_length: number of low bits of K that make up the key
     // key_segment_size: length of a key segment (a key_group)
     pub fn new(key_length: usize, key_segment_size: usize) -> Self {
         // key_length needs to be multiple of key_segment_size
         assert_eq!(key_length % key_segment_size, 0);
+        Self::with_segments(&vec![key_segment_size; key_length / key_segment_size])
+    }//constructor
 
-        let mut memory: Vec<Option<SubTrie<T>>>; //memory is a vector that contains SubTries or None
-        // init with all nodes that is not leaf
-        // nodes_length = summation of KEY_LEN^1 to KEY_LEN^(KEY_LEN/KEY_GROUP-1)
-        {//new block
-            let mut nodes_length = 0;
-            let array_length = usize::pow(2, key_segment_size as u32);
-            let mut multitude = array_length;
-            for _ in 0..(key_length / key_segment_size - 1) { //for the number of segments
-                nodes_length += multitude;
-                multitude *= array_length;
-            }//for
-//            println!("nl {}", nodes_length);
-            memory = Vec::with_capacity(nodes_length); //total memory needed for this KEY_LEN and KEY_GROUP
-
-            //for each index from 0 to the # of nodes
-            for i in 0..nodes_length {
-                memory.push(Some(SubTrie { //add a SubTrie in each index of the memory vector
-                    data: None,
-                    depth: get_depth(key_segment_size as usize, i),
-                    children_offset: Some((i + 1) * array_length as usize),
-                }));
-//                println!("co {} {}", i, (i + 1) * array_length as usize);
-            }//for
-        }//end block
+    //constructor with a segment size per depth, from the root down, such as [16, 8, 8]
+    // the key is as long as the segments together
+    pub fn with_segments(segments: &[usize]) -> Self {
+        assert!(!segments.is_empty());
+        assert!(segments.iter().all(|&size| size > 0 && size < 64));
+        let key_length: usize = segments.iter().sum();
+        // the key has to fit in the key type
+        assert!(key_length <= K::BITS);
+
+        let mut shifts = Vec::with_capacity(segments.len());
+        let mut consumed = 0;
+        for &size in segments {
+            consumed += size;
+            shifts.push(key_length - consumed);
+        }//for
 
-        //return this struct
-        ContiguousTrie {
-            memory,
+        let mut trie = ContiguousTrie {
+            levels: segments.iter().map(|_| BTreeMap::new()).collect(), //one sparse directory of blocks per depth
             key_length,
-            key_segment_size,
-        }//return struct
-    }//constructor
+            segments: segments.to_vec(),
+            shifts,
+            _key: PhantomData,
+        };
+        trie.alloc_block(0, 0); // the root block
+        trie
+    }//with_segments
 
-    // return the index in the first <= 4 bits
-    // for instances: 0000 0000 -> 0
+    // number of slots in a block at the given depth
     #[inline(always)]
-    fn compute_index(&self, key: &[u8]) -> usize {
-        let mut id = 0;
-        let length = if key.len() > self.key_segment_size { self.key_segment_size } else { key.len() };
-        for i in 0..length {
-            let temp = key[i] as usize - '0' as usize;
-            id += temp << (length - i - 1); // shifts to convert array into binary binary_format
-                                            // e.g. {1,0,1,0} => 1010
-        }//for
-        return id as usize;
+    fn array_length(&self, depth: usize) -> usize {
+        1 << self.segments[depth]
+    }//array_length
+
+    // number of segments in a key, the last one indexes the leaf
+    #[inline(always)]
+    fn levels(&self) -> usize {
+        self.segments.len()
+    }//levels
+
+    // the offset of the block that holds the slot at the given offset of its level
+    #[inline(always)]
+    fn block_of(&self, offset: u64, depth: usize) -> u64 {
+        offset & !((1 << self.segments[depth]) - 1)
+    }//block_of
+
+    // allocate the empty block at the given offset of its level
+    // the block keeps that offset, see the notes on new at the top of this file
+    fn alloc_block(&mut self, depth: usize, start: u64) {
+        let block = vec![None; self.array_length(depth)].into_boxed_slice();
+        self.levels[depth].insert(start, block);
+    }//alloc_block
+
+    // the number of slots allocated so far, which grows with the populated key space
+    pub fn allocated_slots(&self) -> usize {
+        self.levels.iter().enumerate().map(|(depth, blocks)| blocks.len() << self.segments[depth]).sum()
+    }//allocated_slots
+
+    // the heap memory held by the trie, the directory entries of the blocks included
+    pub fn size_in_bytes(&self) -> usize {
+        let blocks: usize = self.levels.iter().map(|blocks| blocks.len()).sum();
+        self.allocated_slots() * mem::size_of::<Option<SubTrie<V>>>()
+            + blocks * (mem::size_of::<u64>() + mem::size_of::<Block<V>>())
+            + self.segments.len() * 2 * mem::size_of::<usize>()
+    }//size_in_bytes
+
+    // return the index of the segment at the given depth, most significant segment first
+    // for instances, with segments [4, 4]: (0001 1010, 0) -> 1, (0001 1010, 1) -> 10
+    //                with segments [2, 6]: (0001 1010, 0) -> 0, (0001 1010, 1) -> 26
+    #[inline(always)]
+    fn compute_index(&self, key: u64, depth: usize) -> usize {
+        ((key >> self.shifts[depth]) & ((1 << self.segments[depth]) - 1)) as usize
     }//compute_index
 
-    // key should be 1-1 mapping to self memory array
+    // reject keys with bits set above key_length
     #[inline(always)]
-    fn key2index(&self, key: &[u8]) -> (usize, usize) {
-        let mut current_index = self.compute_index(key);
-        let mut key_start = 0;
-        let mut depth = 0;
-        while self.memory.len() > current_index && self.memory[current_index].is_some() {
-//            println!("comp_index {} ci {} {}", self.compute_index(&key[key_start..]), current_index, self.memory.len());
-            match &self.memory[current_index] {
-                Some(a) => {
-                    match a.children_offset {
-                        Some(b) => {
-                            key_start += self.key_segment_size;
-                            depth += 1;
-                            current_index = b + self.compute_index(&key[key_start..]);
-                        }//Some(b)
-                        None => break,
-                    }//match a.children_offset
-                }//Some(a)
-                None => break,
-            }//match &self.memory[current_index]
-        }//while
-        (current_index, depth)
+    fn check(&self, key: K) -> Result<(), TrieError> {
+        if self.key_length < 64 && key.to_u64() >> self.key_length != 0 {
+            return Err(TrieError::OutOfRange);
+        }//if
+        Ok(())
+    }//check
+
+    // key should be 1-1 mapping to the slots of the leaf level
+    // the leaf of a key is at offset key of the last level, so it is found without walking the
+    // interior; return it, or None if its block hasn't been allocated
+    #[inline(always)]
+    fn key2index(&self, key: K) -> Option<&Option<SubTrie<V>>> {
+        let key = key.to_u64();
+        let last = self.levels() - 1;
+        match self.levels[last].get(&self.block_of(key, last)) {
+            Some(block) => Some(&block[self.compute_index(key, last)]),
+            None => None,
+        }//match
     }//key2index
 
-    // insert the entry to hash trie
-    pub fn insert(&mut self, value: T, key: &[u8]) {
-        let index_depth_pair = self.key2index(key); // (current_index, depth)
-//        println!("debug {} {}", index_depth_pair, self.memory.len());
-        if index_depth_pair.0 >= self.memory.len() {
-            let push_amount = index_depth_pair.0 - self.memory.len() + 1;
-            for _ in 0..push_amount {
-                self.memory.push(None);
+    // same as key2index, allocating the missing blocks on the way
+    // a block is allocated together with the interior slot that leads to it, so the blocks of the
+    // path of a key exist as soon as its leaf block does
+    fn key2index_mut(&mut self, key: K) -> &mut Option<SubTrie<V>> {
+        let key = key.to_u64();
+        let last = self.levels() - 1;
+        let start = self.block_of(key, last);
+        if !self.levels[last].contains_key(&start) {
+            for depth in 0..last {
+                let offset = key >> self.shifts[depth];
+                let block = self.block_of(offset, depth);
+                // children_offset = (i + 1) * array_length, numbered from the start of the level below
+                let child = offset << self.segments[depth + 1];
+                let missing = {
+                    let slot = &mut self.levels[depth].get_mut(&block).unwrap()[(offset - block) as usize];
+                    let missing = slot.is_none();
+                    if missing {
+                        *slot = Some(SubTrie {
+                            data: None,
+                            depth,
+                            children_offset: Some(child),
+                        });
+                    }//if
+                    missing
+                };
+                if missing {
+                    self.alloc_block(depth + 1, child);
+                }//if
             }//for
         }//if
-        if self.memory[index_depth_pair.0].is_some() {
-            assert!(false);
-        }//if
-        self.memory[index_depth_pair.0] = Some(SubTrie {
+        let slot = self.compute_index(key, last);
+        &mut self.levels[last].get_mut(&start).unwrap()[slot]
+    }//key2index_mut
+
+    // insert the entry to hash trie
+    // return the value that was stored under the key before, if any
+    pub fn insert(&mut self, value: V, key: K) -> Result<Option<V>, TrieError> {
+        self.check(key)?;
+        let leaf = Some(SubTrie {
             data: Some(value),
-            depth: index_depth_pair.1,
+            depth: self.levels() - 1,
             children_offset: None,
-        });// self.memory
+        });
+        Ok(mem::replace(self.key2index_mut(key), leaf).and_then(|old| old.data))
     }//insert
 
+    // insert the entry only if the key isn't in the trie yet
+    pub fn try_insert(&mut self, value: V, key: K) -> Result<(), TrieError> {
+        self.check(key)?;
+        let depth = self.levels() - 1;
+        let slot = self.key2index_mut(key);
+        if slot.is_some() {
+            return Err(TrieError::Duplicate);
+        }//if
+        *slot = Some(SubTrie {
+            data: Some(value),
+            depth,
+            children_offset: None,
+        });
+        Ok(())
+    }//try_insert
+
     // return true if the key entry exists
     #[inline(always)]
-    pub fn contain(&self, key: &[u8]) -> bool {
-        let index_depth_pair = self.key2index(key); // (current_index, depth)
-        if self.memory.len() <= index_depth_pair.0 {
-            return false; // can't contain the key entry if the key index is greater than the length of memory
+    pub fn contain(&self, key: K) -> bool {
+        if self.check(key).is_err() {
+            return false; // a key that doesn't fit can't be in the trie
         }//if
-        match &self.memory[index_depth_pair.0] {
-            Some(_) => {
-                true //if there's something there, we have an entry
-            }
-            None => false, //otherwise, we don't have an entry
+        match self.key2index(key) {
+            Some(slot) => slot.is_some(), //if there's something there, we have an entry
+            None => false, // can't contain the key entry if its block was never allocated
         }//match
     }//contain
 
-    // return the value in the given key and wrap it with an Option
+    // return the value in the given key and wrap it with an Option, or an error for a key that doesn't fit
     #[inline(always)]
-    pub fn get(&self, key: &[u8]) -> Option<T> {
-        let index_depth_pair = self.key2index(key); // (current_index, depth)
-        if self.memory.len() <= index_depth_pair.0 {
-            return None; // can't return anything if we're out of memory bounds
-        }//if
-        match &self.memory[index_depth_pair.0] {
-            Some(a) => {
-                a.data // return the data at the given index
-            }
-            None => None, // if there's nothing at the given index, return None
+    pub fn get(&self, key: K) -> Result<Option<V>, TrieError> {
+        self.check(key)?;
+        match self.key2index(key) {
+            Some(slot) => match slot {
+                Some(a) => Ok(a.data), // return the data at the given index
+                None => Ok(None), // if there's nothing at the given index, return None
+            },
+            None => Ok(None), // can't return anything if the key's block was never allocated
         }//match
     }//get
 
-    // insert_: performs the insert function with a string, rather than &[u8]
-    pub fn insert_(&mut self, value: T, key: String) {
-        let arr = key.to_owned().into_bytes();
-        self.insert(value, &arr[2..]);
-    }//insert_
+    // remove the entry and return its value
+    // the blocks on its path stay allocated until shrink_to_fit
+    pub fn remove(&mut self, key: K) -> Result<Option<V>, TrieError> {
+        self.check(key)?;
+        let last = self.levels() - 1;
+        let start = self.block_of(key.to_u64(), last);
+        let slot = self.compute_index(key.to_u64(), last);
+        match self.levels[last].get_mut(&start) {
+            Some(block) => Ok(block[slot].take().and_then(|old| old.data)),
+            None => Ok(None), // the key's block was never allocated
+        }//match
+    }//remove
+
+    // release the blocks that no longer hold any entry
+    // from the leaves up, so that a block whose children were all released goes as well; the
+    // interior slot that led to a released block is cleared
+    pub fn shrink_to_fit(&mut self) {
+        for depth in (1..self.levels()).rev() {
+            let empty: Vec<u64> = self.levels[depth].iter()
+                .filter(|&(_, block)| block.iter().all(|slot| slot.is_none()))
+                .map(|(&start, _)| start)
+                .collect();
+            for start in empty {
+                self.levels[depth].remove(&start);
+                let parent = start >> self.segments[depth];
+                let block = self.block_of(parent, depth - 1);
+                if let Some(slots) = self.levels[depth - 1].get_mut(&block) {
+                    slots[(parent - block) as usize] = None;
+                }//if
+            }//for
+        }//for
+    }//shrink_to_fit
+
+    // pack the trie into its compact read-only form
+    // the interior blocks become arrays of child block starts, taken in key order, the leaf
+    // blocks a bitmap of the occupied slots, and the values are stored densely in key order
+    pub fn freeze(mut self) -> FrozenContiguousTrie<K, V> {
+        self.shrink_to_fit(); // so that every interior slot left leads to at least one value
+        let last = self.levels() - 1;
+        let mut children = Vec::new();
+        for depth in 0..last {
+            // where the blocks of the next depth start: in children for an interior depth,
+            // in the leaf bitmap for the last one
+            let next_start = if depth + 1 < last { children.len() + (self.levels[depth].len() << self.segments[depth]) } else { 0 };
+            // the child blocks are the blocks of the next depth, in the same key order
+            let mut next = 0;
+            for block in self.levels[depth].values() {
+                for slot in block.iter() {
+                    match slot {
+                        Some(SubTrie { children_offset: Some(_), .. }) => {
+                            let start = next_start + (next << self.segments[depth + 1]);
+                            assert!(start < NO_CHILD as usize, "too many blocks to freeze");
+                            children.push(start as u32);
+                            next += 1;
+                        }//Some(b)
+                        _ => children.push(NO_CHILD),
+                    }//match slot
+                }//for
+            }//for
+        }//for
+
+        let mut words: Vec<RankWord> = Vec::new();
+        let mut prefixes = Vec::with_capacity(self.levels[last].len());
+        let mut values = Vec::new();
+        let mut position = 0; // bit of the current slot
+        for (&start, block) in self.levels[last].iter() {
+            prefixes.push(start); // a leaf block starts at its key with the last segment cleared
+            for slot in block.iter() {
+                if position % 64 == 0 {
+                    words.push(RankWord { bits: 0, rank: values.len() as u64 });
+                }//if
+                if let Some(SubTrie { data: Some(value), .. }) = slot {
+                    words.last_mut().unwrap().bits |= 1 << (position % 64);
+                    values.push(*value);
+                }//if
+                position += 1;
+            }//for
+        }//for
+        children.shrink_to_fit();
+        words.shrink_to_fit();
+        values.shrink_to_fit();
+
+        FrozenContiguousTrie {
+            children,
+            words,
+            prefixes,
+            values,
+            key_length: self.key_length,
+            segments: self.segments,
+            shifts: self.shifts,
+            _key: PhantomData,
+        }
+    }//freeze
+
+    // write the trie to a file that MappedContiguousTrie::open maps back without deserializing
+    // the blocks are written level by level in key order, so a child block sits at the start of
+    // its level in the file plus its rank among the blocks of that level, see src/mapped_cchamt.rs
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> where V: Pod {
+        let mut bases = Vec::with_capacity(self.levels() + 1); // the start of every level in the file
+        let mut base = 0;
+        for (depth, blocks) in self.levels.iter().enumerate() {
+            bases.push(base);
+            base += blocks.len() << self.segments[depth];
+        }//for
+        let segments = &self.segments;
+        let mut ranks = vec![0; self.levels()]; // the child blocks written so far, per depth
+        let slots = self.levels.iter().enumerate()
+            .flat_map(|(depth, blocks)| blocks.values().flat_map(|block| block.iter()).map(move |slot| (depth, slot)))
+            .map(|(depth, slot)| match slot {
+                Some(SubTrie { children_offset: Some(_), .. }) => {
+                    let child = bases[depth + 1] + (ranks[depth] << segments[depth + 1]);
+                    ranks[depth] += 1;
+                    Entry::Child(child)
+                }//Some(b)
+                Some(SubTrie { data: Some(value), .. }) => Entry::Leaf(*value),
+                _ => Entry::Empty,
+            });
+        mapped_cchamt::write(path.as_ref(), K::BITS, &self.segments, slots)
+    }//save
+
+    // the largest key that fits in key_length bits
+    #[inline(always)]
+    fn max_key(&self) -> u64 {
+        if self.key_length == 64 { u64::max_value() } else { (1 << self.key_length) - 1 }
+    }//max_key
+
+    // an iterator over the keys in [lo, hi], which have to fit in key_length
+    fn iter_between(&self, lo: u64, hi: u64) -> ContiguousIter<K, V> {
+        let last = self.levels() - 1;
+        ContiguousIter {
+            blocks: self.levels[last].range(self.block_of(lo, last)..=self.block_of(hi, last)),
+            lo,
+            hi,
+            front: None,
+            back: None,
+            _key: PhantomData,
+        }
+    }//iter_between
+
+    // an iterator that yields nothing
+    fn iter_empty(&self) -> ContiguousIter<K, V> {
+        ContiguousIter {
+            blocks: self.levels[self.levels() - 1].range(0..0),
+            lo: 0,
+            hi: 0,
+            front: None,
+            back: None,
+            _key: PhantomData,
+        }
+    }//iter_empty
+
+    // iterate over the (key, value) pairs in ascending key order
+    pub fn iter(&self) -> ContiguousIter<K, V> {
+        self.iter_between(0, self.max_key())
+    }//iter
+
+    // iterate over the (key, value) pairs with keys in range, in ascending key order
+    // the leaf of a key is at offset key of the last level, so the keys in range are the slots
+    // [lo, hi] of that level: the leaf blocks in the window are taken from the directory, and
+    // the slots of each, cut to the window, are scanned as a slice; bounds that don't fit in
+    // key_length are clamped
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> ContiguousIter<K, V> {
+        let lo = match range.start_bound() {
+            Bound::Included(k) => Some(k.to_u64()),
+            Bound::Excluded(k) => k.to_u64().checked_add(1),
+            Bound::Unbounded => Some(0),
+        };//lo
+        let hi = match range.end_bound() {
+            Bound::Included(k) => Some(k.to_u64()),
+            Bound::Excluded(k) => k.to_u64().checked_sub(1),
+            Bound::Unbounded => Some(u64::max_value()),
+        };//hi
+
+        match (lo, hi) {
+            (Some(lo), Some(hi)) if lo <= hi && lo <= self.max_key() => self.iter_between(lo, cmp::min(hi, self.max_key())),
+            _ => self.iter_empty(),
+        }//match
+    }//range
+
+    // iterate over the entries whose top len bits (of key_length) are bits, in ascending key order
+    // for instances, with key_length 32: prefix(0x0a01, 16) visits the keys 0x0a01_0000..=0x0a01_ffff
+    pub fn prefix(&self, bits: K, len: usize) -> ContiguousIter<K, V> {
+        assert!(len <= self.key_length, "prefix is longer than the key");
+        if len == 0 {
+            return self.iter();
+        }//if
+        let bits = bits.to_u64();
+        if len < 64 && bits >> len != 0 {
+            // no key starts with a prefix that doesn't fit in len bits
+            return self.iter_empty();
+        }//if
+        let lo = bits << (self.key_length - len);
+        let hi = lo | self.max_key().checked_shr(len as u32).unwrap_or(0); // a 64-bit prefix is a single key
+        self.iter_between(lo, hi)
+    }//prefix
+
+    // iterate over the keys in ascending order
+    pub fn keys(&self) -> ContiguousKeys<K, V> {
+        ContiguousKeys { inner: self.iter() }
+    }//keys
+
+    // iterate over the values in ascending key order
+    pub fn values(&self) -> ContiguousValues<K, V> {
+        ContiguousValues { inner: self.iter() }
+    }//values
 
 }//impl ContiguousTrie
 
+// the slots of a leaf block cut to the keys of an iterator, with the key of the first one
+type Window<'a, V> = (u64, Enumerate<slice::Iter<'a, Option<SubTrie<V>>>>);
+
+// the next entry of a window, from the front or from the back
+fn window_next<K: PrimInt, V: TrieData>(window: &mut Option<Window<V>>, forward: bool) -> Option<(K, V)> {
+    if let Some((base, ref mut slots)) = *window {
+        loop {
+            let next = if forward { slots.next() } else { slots.next_back() };
+            match next {
+                Some((i, &Some(SubTrie { data: Some(value), .. }))) => return Some((K::from_u64(base + i as u64), value)),
+                Some(_) => {}
+                None => break,
+            }//match next
+        }//loop
+    }//if
+    None
+}//window_next
+
+// iterator over the (key, value) pairs of a ContiguousTrie in key order
+// the keys in [lo, hi] are visited; each end holds the window of the leaf block it is in, see
+// ContiguousTrie::range, and takes the next block from the directory once it is done with it
+pub struct ContiguousIter<'a, K: PrimInt + 'a, V: TrieData + 'a> {
+    blocks: btree_map::Range<'a, u64, Block<V>>, // the leaf blocks neither end has reached yet
+    lo: u64,
+    hi: u64,
+    front: Option<Window<'a, V>>,
+    back: Option<Window<'a, V>>,
+    _key: PhantomData<K>,
+}//struct ContiguousIter
+
+impl<'a, K: PrimInt, V: TrieData> ContiguousIter<'a, K, V> {
+    // the slots of the leaf block at start that hold keys in [lo, hi]
+    fn window(&self, start: u64, block: &'a Block<V>) -> Window<'a, V> {
+        let first = if self.lo > start { (self.lo - start) as usize } else { 0 };
+        let last = cmp::min(self.hi - start, block.len() as u64 - 1) as usize;
+        (start + first as u64, block[first..=last].iter().enumerate())
+    }//window
+}//impl ContiguousIter
+
+impl<'a, K: PrimInt, V: TrieData> Iterator for ContiguousIter<'a, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        loop {
+            if let Some(entry) = window_next(&mut self.front, true) {
+                return Some(entry);
+            }//if
+            match self.blocks.next() {
+                Some((&start, block)) => self.front = Some(self.window(start, block)),
+                None => return window_next(&mut self.back, true), // the back end has the last block
+            }//match
+        }//loop
+    }//next
+}//impl Iterator for ContiguousIter
+
+impl<'a, K: PrimInt, V: TrieData> DoubleEndedIterator for ContiguousIter<'a, K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        loop {
+            if let Some(entry) = window_next(&mut self.back, false) {
+                return Some(entry);
+            }//if
+            match self.blocks.next_back() {
+                Some((&start, block)) => self.back = Some(self.window(start, block)),
+                None => return window_next(&mut self.front, false), // the front end has the first block
+            }//match
+        }//loop
+    }//next_back
+}//impl DoubleEndedIterator for ContiguousIter
+
+impl<'a, K: PrimInt, V: TrieData> IntoIterator for &'a ContiguousTrie<K, V> {
+    type Item = (K, V);
+    type IntoIter = ContiguousIter<'a, K, V>;
+
+    fn into_iter(self) -> ContiguousIter<'a, K, V> {
+        self.iter()
+    }//into_iter
+}//impl IntoIterator for &ContiguousTrie
+
+// iterator over the keys of a ContiguousTrie in key order
+pub struct ContiguousKeys<'a, K: PrimInt + 'a, V: TrieData + 'a> {
+    inner: ContiguousIter<'a, K, V>,
+}//struct ContiguousKeys
+
+impl<'a, K: PrimInt, V: TrieData> Iterator for ContiguousKeys<'a, K, V> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.inner.next().map(|(key, _)| key)
+    }//next
+}//impl Iterator for ContiguousKeys
+
+impl<'a, K: PrimInt, V: TrieData> DoubleEndedIterator for ContiguousKeys<'a, K, V> {
+    fn next_back(&mut self) -> Option<K> {
+        self.inner.next_back().map(|(key, _)| key)
+    }//next_back
+}//impl DoubleEndedIterator for ContiguousKeys
+
+// iterator over the values of a ContiguousTrie in key order
+pub struct ContiguousValues<'a, K: PrimInt + 'a, V: TrieData + 'a> {
+    inner: ContiguousIter<'a, K, V>,
+}//struct ContiguousValues
+
+impl<'a, K: PrimInt, V: TrieData> Iterator for ContiguousValues<'a, K, V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.inner.next().map(|(_, value)| value)
+    }//next
+}//impl Iterator for ContiguousValues
+
+impl<'a, K: PrimInt, V: TrieData> DoubleEndedIterator for ContiguousValues<'a, K, V> {
+    fn next_back(&mut self) -> Option<V> {
+        self.inner.next_back().map(|(_, value)| value)
+    }//next_back
+}//impl DoubleEndedIterator for ContiguousValues
+
+// the child entry of an interior slot without a block below it
+const NO_CHILD: u32 = u32::max_value();
+
+// 64 slots of a bitmap together with the number of set bits before them,
+// so a rank costs a single memory access
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+struct RankWord {
+    bits: u64,
+    rank: u64,
+}//struct RankWord
+
+/// Read-only form of a ContiguousTrie, see ContiguousTrie::freeze
+/// The interior depths are stored one after the other in children, a block of 2^segments[d]
+/// entries per block of depth d, each the start of the child block (in children, or in the leaf
+/// bitmap below the last interior depth) or NO_CHILD, so the walk is the same as ContiguousTrie's.
+/// The leaf blocks are a bitmap with a bit per slot, and the n-th set bit is the key of values[n],
+/// so the value of a leaf is found with a rank, and the leaf of a value with a select.
+/// Immutable, so it can be shared between reader threads as is.
+#[derive(Debug)]
+pub struct FrozenContiguousTrie<K: PrimInt, V: TrieData> {
+    children: Vec<u32>,
+    words: Vec<RankWord>,
+    prefixes: Vec<u64>, // the key bits above the leaf segment, for every leaf block
+    values: Vec<V>,
+    key_length: usize,
+    segments: Vec<usize>,
+    shifts: Vec<usize>,
+    _key: PhantomData<K>,
+}//struct FrozenContiguousTrie
+
+impl<K: PrimInt, V: TrieData> FrozenContiguousTrie<K, V> {
+    #[inline(always)]
+    fn compute_index(&self, key: u64, depth: usize) -> usize {
+        ((key >> self.shifts[depth]) & ((1 << self.segments[depth]) - 1)) as usize
+    }//compute_index
+
+    // the number of entries
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }//len
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }//is_empty
+
+    // the heap memory held by the trie
+    pub fn size_in_bytes(&self) -> usize {
+        self.children.capacity() * mem::size_of::<u32>()
+            + self.words.capacity() * mem::size_of::<RankWord>()
+            + self.prefixes.capacity() * mem::size_of::<u64>()
+            + self.values.capacity() * mem::size_of::<V>()
+            + self.segments.len() * 2 * mem::size_of::<usize>()
+    }//size_in_bytes
+
+    // return the position of the key in values
+    #[inline(always)]
+    fn key2index(&self, key: u64) -> Option<usize> {
+        let mut position = self.compute_index(key, 0);
+        for depth in 1..self.segments.len() {
+            let child = self.children[position];
+            if child == NO_CHILD {
+                return None;
+            }//if
+            position = child as usize + self.compute_index(key, depth);
+        }//for
+        let word = self.words[position / 64];
+        if word.bits == u64::max_value() {
+            // dense keys fill whole words, and then the rank needs no popcount
+            return Some(word.rank as usize + position % 64);
+        }//if
+        let bit = 1 << (position % 64);
+        if word.bits & bit == 0 {
+            return None;
+        }//if
+        Some(word.rank as usize + (word.bits & (bit - 1)).count_ones() as usize)
+    }//key2index
+
+    // position of the k-th set bit of the leaf bitmap
+    fn select(&self, k: usize) -> usize {
+        // the last word with at most k set bits before it holds the k-th one
+        let (mut lo, mut hi) = (0, self.words.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.words[mid].rank as usize <= k {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }//if
+        }//while
+        let i = lo - 1;
+        let mut bits = self.words[i].bits;
+        for _ in 0..(k - self.words[i].rank as usize) {
+            bits &= bits - 1; // clear the lowest set bit
+        }//for
+        i * 64 + bits.trailing_zeros() as usize
+    }//select
+
+    // rebuild the key of values[n] from the leaf block holding it
+    fn key_at(&self, n: usize) -> K {
+        let segment = self.segments[self.segments.len() - 1];
+        let position = self.select(n);
+        let slot = position & ((1 << segment) - 1);
+        K::from_u64(self.prefixes[position >> segment] | slot as u64)
+    }//key_at
+
+    // return the value in the given key and wrap it with an Option, or an error for a key that doesn't fit
+    #[inline(always)]
+    pub fn get(&self, key: K) -> Result<Option<V>, TrieError> {
+        let key = key.to_u64();
+        if self.key_length < 64 && key >> self.key_length != 0 {
+            return Err(TrieError::OutOfRange);
+        }//if
+        Ok(self.key2index(key).map(|n| self.values[n]))
+    }//get
+
+    // return true if the key entry exists
+    #[inline(always)]
+    pub fn contain(&self, key: K) -> bool {
+        match self.get(key) {
+            Ok(found) => found.is_some(),
+            Err(_) => false,
+        }//match
+    }//contain
+
+    // iterate over the (key, value) pairs in ascending key order
+    pub fn iter(&self) -> FrozenIter<K, V> {
+        FrozenIter { trie: self, front: 0, back: self.values.len() }
+    }//iter
+}//impl FrozenContiguousTrie
+
+// iterator over the (key, value) pairs of a FrozenContiguousTrie in key order
+pub struct FrozenIter<'a, K: PrimInt + 'a, V: TrieData + 'a> {
+    trie: &'a FrozenContiguousTrie<K, V>,
+    front: usize,
+    back: usize, // one past the last value still to visit
+}//struct FrozenIter
+
+impl<'a, K: PrimInt, V: TrieData> Iterator for FrozenIter<'a, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        if self.front == self.back {
+            return None;
+        }//if
+        let n = self.front;
+        self.front += 1;
+        Some((self.trie.key_at(n), self.trie.values[n]))
+    }//next
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.back - self.front, Some(self.back - self.front))
+    }//size_hint
+}//impl Iterator for FrozenIter
+
+impl<'a, K: PrimInt, V: TrieData> DoubleEndedIterator for FrozenIter<'a, K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        if self.front == self.back {
+            return None;
+        }//if
+        self.back -= 1;
+        Some((self.trie.key_at(self.back), self.trie.values[self.back]))
+    }//next_back
+}//impl DoubleEndedIterator for FrozenIter
+
 // TODO should change this to key_length+2, which is {:0key_length+2b}
 #[macro_export]
 macro_rules! binary_format {
@@ -250,18 +836,13 @@ macro_rules! binary_format {
 }//binary_format
 
 fn main() {
-    let mut trie = ContiguousTrie::<usize>::new(32, 8);
+    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 
     for i in 0..100000 {
-        let str = binary_format!(i);
-//        println!("{}", str);
-        let arr = str.to_owned().into_bytes();
-        trie.insert(i, &arr[2..]);
+        trie.insert(i as usize, i).unwrap();
     }
 
     for i in 0..100000 {
-        let str = binary_format!(i);
-        let arr = str.to_owned().into_bytes();
-        assert_eq!(trie.get(&arr[2..]).unwrap(), i);
+        assert_eq!(trie.get(i), Ok(Some(i as usize)));
     }
 }
@@ -0,0 +1,30 @@
+use std::error::Error;
+use std::fmt;
+
+//returned by the contiguous tries when an operation can't be carried out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrieError {
+    InvalidDigit, //a bit-string key holds a byte other than '0' or '1'
+    WrongLength, //a bit-string key isn't key_length digits long
+    Duplicate, //try_insert found the key already present
+    OutOfRange, //an integer key has bits set above key_length
+    Full, //a trie with preallocated memory has no room left for a new block
+}
+
+impl fmt::Display for TrieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+impl Error for TrieError {
+    fn description(&self) -> &str {
+        match *self {
+            TrieError::InvalidDigit => "key contains a digit other than '0' or '1'",
+            TrieError::WrongLength => "key length doesn't match the trie's key length",
+            TrieError::Duplicate => "key is already in the trie",
+            TrieError::OutOfRange => "key doesn't fit in the trie's key length",
+            TrieError::Full => "trie has no memory left for a new block",
+        }
+    }
+}
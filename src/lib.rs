@@ -18,15 +18,25 @@ extern crate rayon;
 
 mod hamt;
 mod cchamt;
+mod byte_cchamt;
 //mod bench;
 mod allocator;
 mod lockfree_cchamt;
 mod mutex_cchamt;
 mod rwlock_cchamt;
+mod mapped_cchamt;
+mod atomic_cchamt;
+mod striped_cchamt;
+mod error;
 
 pub use hamt::{Trie, TrieData, IndexStatus};
-pub use cchamt::ContiguousTrie;
-pub use allocator::Allocator;
-pub use lockfree_cchamt::LockfreeTrie;
+pub use cchamt::{ContiguousTrie, PrimInt, ContiguousIter, ContiguousKeys, ContiguousValues, FrozenContiguousTrie, FrozenIter};
+pub use byte_cchamt::{ByteContiguousTrie, ByteIter};
+pub use mapped_cchamt::{MappedContiguousTrie, Pod};
+pub use allocator::{Allocator, Region, NodeAllocator, BoxAllocator, MmapAllocator, DebugAllocator, Usage, AllocError};
+pub use lockfree_cchamt::{LockfreeTrie, OrderedKey, RangeIter, MemoryUsage, NodeCounts, Node, ANode};
 pub use mutex_cchamt::MutexContiguousTrie;
 pub use rwlock_cchamt::RwContiguousTrie;
+pub use atomic_cchamt::{AtomicContiguousTrie, PackedValue};
+pub use striped_cchamt::StripedContiguousTrie;
+pub use error::TrieError;
@@ -1,10 +1,17 @@
 use std::hash::{Hash, Hasher};
 use std::collections::hash_map::DefaultHasher;
-use std::sync::atomic::{AtomicPtr, Ordering, AtomicU32};
+use std::sync::atomic::{AtomicPtr, Ordering, AtomicU32, AtomicBool};
+use std::sync::Mutex;
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::option::Option;
 use std::ptr::null_mut;
-use allocator::Allocator;
+use std::ops::{Bound, RangeBounds, Index, IndexMut};
+use std::slice;
+use std::mem;
+use allocator::{Allocator, NodeAllocator, Region, Usage, AllocError};
 use std::thread;
+use core::intrinsics::prefetch_read_data;
 
 pub trait TrieData: Clone + Copy + Eq + PartialEq {}
 
@@ -14,12 +21,102 @@ pub trait TrieKey: Clone + Copy + Eq + PartialEq + Hash {}
 
 impl<T> TrieKey for T where T: Clone + Copy + Eq + PartialEq + Hash {}
 
+/// Integer keys that can be placed in the trie by their own bits instead of their hash.
+/// key_bits returns the key left-aligned in a u64, so that comparing the bits as unsigned
+/// integers gives the same order as comparing the keys.
+pub trait OrderedKey: TrieKey {
+    fn key_bits(&self) -> u64;
+}
+
+macro_rules! ordered_key_unsigned {
+    ($($t:ty),*) => {
+        $(impl OrderedKey for $t {
+            #[inline(always)]
+            fn key_bits(&self) -> u64 {
+                (*self as u64) << (64 - 8 * mem::size_of::<$t>())
+            }
+        })*
+    };
+}
+
+// signed keys flip the sign bit so that negative keys sort before positive ones
+macro_rules! ordered_key_signed {
+    ($($t:ty => $u:ty),*) => {
+        $(impl OrderedKey for $t {
+            #[inline(always)]
+            fn key_bits(&self) -> u64 {
+                ((*self as $u) ^ (1 << (8 * mem::size_of::<$t>() - 1))).key_bits()
+            }
+        })*
+    };
+}
+
+ordered_key_unsigned!(u8, u16, u32, u64, usize);
+ordered_key_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, isize => usize);
+
+//the slots of an array node are fixed arrays aligned to a cache line: a narrow array fills
+//one line, and the slot a lookup reads in a wide one never straddles two
+//they are allocated apart from the node, each width in its own size class, so that the
+//arena slot of every node stays as small as an SNode
+#[repr(align(64))]
+pub struct NarrowSlots<K, V>([AtomicPtr<Node<K, V>>; 4]);
+
+#[repr(align(64))]
+pub struct WideSlots<K, V>([AtomicPtr<Node<K, V>>; 16]);
+
 //#[derive(Clone)]
-type ANode<K, V> = Vec<AtomicPtr<Node<K, V>>>;
+pub enum ANode<K, V> {
+    Narrow(Box<NarrowSlots<K, V>>), //4 slots, indexed by the lower 2 bits of a level
+    Wide(Box<WideSlots<K, V>>), //16 slots, indexed by all 4 bits of a level
+}//enum ANode
+
+impl<K, V> ANode<K, V> {
+    fn slots(&self) -> &[AtomicPtr<Node<K, V>>] {
+        match *self {
+            ANode::Narrow(ref narrow) => &narrow.0,
+            ANode::Wide(ref wide) => &wide.0,
+        }//match
+    }//slots
+
+    fn len(&self) -> usize {
+        self.slots().len()
+    }//len
+
+    fn iter(&self) -> slice::Iter<AtomicPtr<Node<K, V>>> {
+        self.slots().iter()
+    }//iter
+}//impl ANode
+
+impl<K, V> Index<usize> for ANode<K, V> {
+    type Output = AtomicPtr<Node<K, V>>;
+
+    fn index(&self, pos: usize) -> &AtomicPtr<Node<K, V>> {
+        &self.slots()[pos]
+    }//index
+}//impl Index for ANode
+
+impl<K, V> IndexMut<usize> for ANode<K, V> {
+    fn index_mut(&mut self, pos: usize) -> &mut AtomicPtr<Node<K, V>> {
+        match *self {
+            ANode::Narrow(ref mut narrow) => &mut narrow.0[pos],
+            ANode::Wide(ref mut wide) => &mut wide.0[pos],
+        }//match
+    }//index_mut
+}//impl IndexMut for ANode
+
+impl<'a, K, V> IntoIterator for &'a ANode<K, V> {
+    type Item = &'a AtomicPtr<Node<K, V>>;
+    type IntoIter = slice::Iter<'a, AtomicPtr<Node<K, V>>>;
+
+    fn into_iter(self) -> slice::Iter<'a, AtomicPtr<Node<K, V>>> {
+        self.iter()
+    }//into_iter
+}//impl IntoIterator for ANode
 
 //nodes can be of 7 distinct types:
+//public so that LockfreeTrie can name it in its allocator parameter and callers can size an arena
 //#[derive(Clone)]
-enum Node<K, V> {
+pub enum Node<K, V> {
     SNode { //stores data
         hash: u64,
         key: K,
@@ -65,9 +162,62 @@ fn hash<T>(obj: T) -> u64
     hasher.finish() //return hashcode for included items(i.e., obj)
 }//hash
 
+// reverse_nibbles: swap the order of the 4-bit groups of bits, so the most significant
+//  nibble ends up in the lowest 4 bits (the trie consumes the hashcode from the low end)
+#[inline(always)]
+fn reverse_nibbles(bits: u64) -> u64 {
+    let mut rev = 0;
+    for i in 0..16 {
+        rev |= ((bits >> (60 - 4 * i)) & 0xf) << (4 * i);
+    }//for
+    rev
+}//reverse_nibbles
+
+// hash_key: hashcode used by the default (hashed) mode
+fn hash_key<K: TrieKey>(key: &K) -> u64 {
+    hash(key)
+}//hash_key
+
+// ordered_hash: hashcode used by the order-preserving mode, the key's own bits with the
+//  most significant nibble first, so level 0 of the trie splits on the top 4 bits of the key
+fn ordered_hash<K: OrderedKey>(key: &K) -> u64 {
+    reverse_nibbles(key.key_bits())
+}//ordered_hash
+
 //maximum # of allowable misses
 const MAX_MISSES: u32 = 2048;   // play with this
 
+//number of lookups get_many keeps in flight at once; enough to cover the memory latency
+//without running out of line fill buffers
+const PREFETCH_GROUP: usize = 16;
+
+// prefetch: hint the cpu to pull the cache line at ptr into all cache levels
+#[inline(always)]
+fn prefetch<T>(ptr: *const T) {
+    unsafe { prefetch_read_data(ptr, 3) }
+}//prefetch
+
+//default number of arena slots of a LockfreeTrie; the arena is mapped lazily, so this only
+//reserves address space, and memory is used as nodes are allocated
+const DEFAULT_CAPACITY: usize = 100000000;
+
+//while profiling, one in every SAMPLE_PERIOD lookups of a thread records its path for repack
+const SAMPLE_PERIOD: u32 = 64;
+//number of visit counters; nodes are mapped onto them by address
+const HEAT_SLOTS: usize = 1 << 14;
+//repack moves a node if at least 1/HOT_RATIO of the sampled lookups went through it
+const HOT_RATIO: u32 = 64;
+
+thread_local!(static LOOKUPS: Cell<u32> = Cell::new(0));
+
+//one in-flight lookup of get_many
+struct Probe<K, V> {
+    node: *const Node<K, V>, //node to read next (already prefetched), null once finished
+    slot: bool, //true if node is an ANode whose slot for this key has been prefetched
+    h: u64, //hashcode of the key
+    lev: u8, //level of node
+}//struct Probe
+
 //struct for CacheLevel
 struct CacheLevel<K: TrieKey, V: TrieData> {
     parent: AtomicPtr<CacheLevel<K, V>>, //parent CacheLevel
@@ -131,21 +281,112 @@ impl<K: TrieKey, V: TrieData> Cache<K, V> {
 }//impl Cache
 
 //structure for LockfreeTrie; public
-pub struct LockfreeTrie<K: TrieKey, V: TrieData> {
+// A: where the nodes live, see NodeAllocator; the default is the packing arena
+pub struct LockfreeTrie<K: TrieKey, V: TrieData, A = Allocator<Node<K, V>>> {
     root: AtomicPtr<Node<K, V>>, //root node
-    mem: Allocator<Node<K, V>>, //memory allocator
+    mem: A, //memory allocator
     cache: AtomicPtr<CacheLevel<K, V>>, //essentially a Cache struct
+    hasher: fn(&K) -> u64, //maps a key to the bits that pick its position in the trie
+    ordered: bool, //true if keys are placed by their own bits (see new_ordered)
+    heat: Vec<AtomicU32>, //visits of sampled lookups, per node (see repack)
+    profiling: AtomicBool, //true if lookups are sampled (see start_profiling)
+    repacking: Mutex<()>, //held by repack, so two repacks don't copy the same nodes
 }//struct Cache
 
-// makeanode: return an ANode with length len and empty elements
+//number of nodes of each variant
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NodeCounts {
+    pub snode: usize,
+    pub anode: usize,
+    pub notxn: usize,
+    pub fsnode: usize,
+    pub fvnode: usize,
+    pub fnode: usize,
+    pub enode: usize,
+}//struct NodeCounts
+
+impl NodeCounts {
+    fn count<K, V>(&mut self, node: &Node<K, V>) -> () {
+        match *node {
+            Node::SNode { .. } => self.snode += 1,
+            Node::ANode(_) => self.anode += 1,
+            Node::NoTxn => self.notxn += 1,
+            Node::FSNode => self.fsnode += 1,
+            Node::FVNode => self.fvnode += 1,
+            Node::FNode { .. } => self.fnode += 1,
+            Node::ENode { .. } => self.enode += 1,
+        }//match
+    }//count
+
+    pub fn total(&self) -> usize {
+        self.snode + self.anode + self.notxn + self.fsnode + self.fvnode + self.fnode + self.enode
+    }//total
+}//impl NodeCounts
+
+//memory used by a LockfreeTrie, returned by memory_usage
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryUsage {
+    pub allocator: Usage, //slots and bytes of the allocator holding the nodes
+    pub live: NodeCounts, //nodes the allocator holds
+    pub reachable: NodeCounts, //nodes reachable from the root
+}//struct MemoryUsage
+
+impl MemoryUsage {
+    //wasted: nodes that are allocated but no longer part of the trie, e.g. replaced txn markers
+    //  and expansion nodes waiting to be reclaimed
+    pub fn wasted(&self) -> NodeCounts {
+        NodeCounts {
+            snode: self.live.snode - self.reachable.snode,
+            anode: self.live.anode - self.reachable.anode,
+            notxn: self.live.notxn - self.reachable.notxn,
+            fsnode: self.live.fsnode - self.reachable.fsnode,
+            fvnode: self.live.fvnode - self.reachable.fvnode,
+            fnode: self.live.fnode - self.reachable.fnode,
+            enode: self.live.enode - self.reachable.enode,
+        }//NodeCounts
+    }//wasted
+}//impl MemoryUsage
+
+//iterator over the (key, value) pairs returned by LockfreeTrie::range, in key order
+pub struct RangeIter<K, V> {
+    entries: ::std::vec::IntoIter<(u64, K, V)>,
+}//struct RangeIter
+
+impl<K, V> Iterator for RangeIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.entries.next().map(|(_, key, val)| (key, val))
+    }//next
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }//size_hint
+}//impl Iterator for RangeIter
+
+// makeanode: return an ANode with length len (4 or 16) and empty elements
 fn makeanode<K, V>(len: usize) -> ANode<K, V> {
-    let mut a: ANode<K, V> = Vec::with_capacity(len);
-    for i in 0..len {
-        a.push(AtomicPtr::new(null_mut()));
-    }//for
-    a //return the array node
+    //an all-zero AtomicPtr is a null pointer
+    match len {
+        4 => ANode::Narrow(Box::new(NarrowSlots(unsafe { mem::zeroed() }))),
+        16 => ANode::Wide(Box::new(WideSlots(unsafe { mem::zeroed() }))),
+        _ => panic!("array nodes have 4 or 16 slots"),
+    }//match
 } //makeanode
 
+// is_anode, is_snode, is_enode: check the variant of a node without borrowing it any longer
+fn is_anode<K, V>(node: &Node<K, V>) -> bool {
+    if let Node::ANode(_) = *node { true } else { false }
+}
+
+fn is_snode<K, V>(node: &Node<K, V>) -> bool {
+    if let Node::SNode { .. } = *node { true } else { false }
+}
+
+fn is_enode<K, V>(node: &Node<K, V>) -> bool {
+    if let Node::ENode { .. } = *node { true } else { false }
+}
+
 fn get_ary_length<K, V>(cur: &Node<K, V>) -> usize {
     let mut len: usize = 1;
     if let Node::ANode(ref cur2) = cur {
@@ -154,26 +395,12 @@ fn get_ary_length<K, V>(cur: &Node<K, V>) -> usize {
     len
 }
 
-fn is_node_null<K, V>(node: &mut Node<K, V>) -> bool {
-    match node {
-        SNode => { false },
-        ANode => { false },
-        NoTxn => { false },
-        FSNode => { false },
-        FVNode => { false },
-        ENode => { false },
-        _ => { true }
-    }
-}
-
 fn is_oldptr_null<K, V>(cur: &Node<K, V>, pos: usize) -> bool {
     let mut is_null: bool = true;
     if let Node::ANode(ref cur2) = cur {
         let old = &cur2[pos];
         let oldptr = old.load(Ordering::Relaxed);
-        let oldref = unsafe { &mut *oldptr };
-        //is_null = oldref.is_null();
-        is_null = is_node_null(oldref);
+        is_null = oldptr.is_null();
     }
     is_null
 }
@@ -286,8 +513,8 @@ fn get_enode_anptr<K, V>(enode: &Node<K, V>) -> &AtomicPtr<Node<K, V>> {
     anptr
 }
 
-fn get_enode_an<K, V>(enode: &Node<K, V>) -> &Vec<AtomicPtr<Node<K, V>>> {
-    let ary: &Vec<AtomicPtr<Node<K, V>>>;
+fn get_enode_an<K, V>(enode: &Node<K, V>) -> &ANode<K, V> {
+    let ary: &ANode<K, V>;
     if let Node::ENode { ref parent, parentpos, ref narrow, level, wide: ref _wide, .. } = enode {
         let parentref = unsafe { &*parent.load(Ordering::Relaxed) };
         if let Node::ANode(ref an) = parentref {
@@ -361,22 +588,36 @@ fn CAS_check_eq<K, V>(p2p: AtomicPtr<Node<K, V>>, cur: &Node<K, V>, en: *mut Nod
  * TODO: fix memory leaks and use atomic_ref or crossbeam crates
  */
 
-//implementation of LockfreeTrie struct
 impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
     //constructor
     pub fn new() -> Self {
         //let mem = Allocator::new(1000000000);
-        let mem = Allocator::new(100000000); //test
+        LockfreeTrie::with_allocator(Allocator::reserve(DEFAULT_CAPACITY))
+    }//constructor
+}//impl LockfreeTrie
+
+//implementation of LockfreeTrie struct
+impl<K: TrieKey, V: TrieData, A: NodeAllocator<Node<K, V>>> LockfreeTrie<K, V, A> {
+    //constructor
+    // mem: backend holding the nodes, e.g. BoxAllocator or DebugAllocator to compare against the arena
+    pub fn with_allocator(mem: A) -> Self {
         LockfreeTrie {//return this struct
             root: AtomicPtr::new(mem.alloc(Node::ANode(makeanode(16)))),
             mem: mem,
             cache: AtomicPtr::new(null_mut()),
+            hasher: hash_key::<K>,
+            ordered: false,
+            heat: (0..HEAT_SLOTS).map(|_| AtomicU32::new(0)).collect(),
+            profiling: AtomicBool::new(false),
+            repacking: Mutex::new(()),
         }//return struct
-    }//constructor
+    }//with_allocator
 
     //_freeze: lock the elements of an ANode until they can be safely unlocked
     // nnode: must be an ANode, or method will panic!
-    fn _freeze(mem: &Allocator<Node<K, V>>, nnode: &mut Node<K, V>) -> () {
+    // fails only if mem is full; the nodes frozen so far stay frozen, and the next operation
+    // that runs into the expansion finishes freezing them
+    fn _freeze(mem: &A, nnode: &mut Node<K, V>) -> Result<(), AllocError> {
          //let cur be a reference to the items in nnode
          //only continue if the items in nnode match those found in an ANode
         if let Node::ANode(ref cur) = nnode {
@@ -384,39 +625,50 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
             while i < cur.len() { //go through the entire array
                 let node = &cur[i]; //node at position i in array
                 let nodeptr = node.load(Ordering::Relaxed); //ptr to node
-                let noderef = unsafe { &mut *nodeptr }; //ref to node
 
                 i += 1; //increase to move forward; future decreases act as lock
                 if nodeptr.is_null() {
                     //update nodeptr to mem.alloc(Node::FVNode)
-                    if node.compare_and_swap(nodeptr, mem.alloc(Node::FVNode), Ordering::Relaxed) != nodeptr {
+                    let fvnode = mem.try_alloc(Node::FVNode)?;
+                    if node.compare_and_swap(nodeptr, fvnode, Ordering::Relaxed) != nodeptr {
+                        mem.dealloc(fvnode); //never published
                         i -= 1; //lock
                     }//if
-                } else if let Node::SNode { ref txn, .. } = noderef { //if the node is an SNode
+                    continue;
+                }//if
+
+                let noderef = unsafe { &mut *nodeptr }; //ref to node, only once we know it isn't null
+                if let Node::SNode { ref txn, .. } = noderef { //if the node is an SNode
                     let txnptr = txn.load(Ordering::Relaxed);
                     let txnref = unsafe { &mut *txnptr };
                     if let Node::NoTxn = txnref { //if the txn is set to NoTxn
                         //update txnptr to mem.alloc(Node::FSNode)
-                        if txn.compare_and_swap(txnptr, mem.alloc(Node::FSNode), Ordering::Relaxed) != txnptr {
+                        let fsnode = mem.try_alloc(Node::FSNode)?;
+                        if txn.compare_and_swap(txnptr, fsnode, Ordering::Relaxed) != txnptr {
+                            mem.dealloc(fsnode); //never published
                             i -= 1; //lock
-                        }//if
+                        } else {
+                            mem.retire(txnref); //the NoTxn marker has been replaced
+                        }//if-else
                     } else if let Node::FSNode = txnref {} else { //if txnref is a frozen SNode
                         //update nodeptr to txnptr
                         node.compare_and_swap(nodeptr, txnptr, Ordering::Relaxed);
                         i -= 1; //lock
                     }//if-else
                 //} else if let Node::ANode(ref an) = noderef { //if the node is an ANode
-                } else if node_type_eq(Node::ANode(makeanode(4)), noderef) { //if the node is an ANode
+                } else if is_anode(noderef) { //if the node is an ANode
                     //declare a frozen ANode
-                    let fnode = mem.alloc(Node::FNode { frozen: AtomicPtr::new(noderef) });
+                    let fnode = mem.try_alloc(Node::FNode { frozen: AtomicPtr::new(noderef) })?;
                     //update nodeptr to fnode
-                    node.compare_and_swap(nodeptr, fnode, Ordering::Relaxed);
+                    if node.compare_and_swap(nodeptr, fnode, Ordering::Relaxed) != nodeptr {
+                        mem.dealloc(fnode); //never published
+                    }//if
                     i -= 1; //lock
                 } else if let Node::FNode { ref frozen } = noderef { //if the node is an FNode
-                    LockfreeTrie::_freeze(mem, unsafe { &mut *frozen.load(Ordering::Relaxed) });
+                    LockfreeTrie::_freeze(mem, unsafe { &mut *frozen.load(Ordering::Relaxed) })?;
                 } else if let Node::ENode { .. } = noderef { //if the node is an ENode
                     //complete the expansion of the node before proceeding
-                    LockfreeTrie::_complete_expansion(mem, noderef);
+                    LockfreeTrie::_complete_expansion(mem, noderef)?;
                     i -= 1; //lock
                 }//if-else
             }//while
@@ -424,52 +676,53 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
             // this has never happened once, but just to be sure...
             panic!("CORRUPTION: nnode is not an ANode")
         }//if-else
+        Ok(())
     }//_freeze
 
     //_copy: recursively copy elements of a narrow array (4 elements) into a wide array (16 elements)
-    fn _copy(mem: &Allocator<Node<K, V>>, an: &ANode<K, V>, wide: &mut Node<K, V>, lev: u64) -> () {
+    fn _copy(mem: &A, an: &ANode<K, V>, wide: &mut Node<K, V>, lev: u64) -> Result<(), AllocError> {
         for node in an { //for every element in the ANode
             match unsafe { &*node.load(Ordering::Relaxed) } { //match the entry
                 Node::FNode { ref frozen } => { //if we have an FNode, make a ref to the frozen ANode
                     //make a reference ptr to the ANode
                     let frzref = unsafe { &*frozen.load(Ordering::Relaxed) };
                     if let Node::ANode(ref an2) = frzref {
-                        LockfreeTrie::_copy(mem, an2, wide, lev); //recursively copy into this array
+                        LockfreeTrie::_copy(mem, an2, wide, lev)?; //recursively copy into this array
                     } else { //if the node somehow isn't an ANode
                         // this has never happened once, but just to be sure...
                         panic!("CORRUPTION: FNode contains non-ANode")
                     }//if-else
                 } //FNode
                 Node::SNode { hash, key, val, txn } => { //if we have an SNode, copy data indo wide array
-                    LockfreeTrie::_insert(mem, *key, *val, *hash, lev as u8, wide, None);
+                    LockfreeTrie::_insert(mem, *key, *val, *hash, lev as u8, wide, None)?;
                 }//SNode
                 _ => { /* ignore; not an F or S Node */ }
             }//match
         }//for
+        Ok(())
     }//_copy
 
     //_complete_expansion: complete the expansion of an ENode
-    fn _complete_expansion(mem: &Allocator<Node<K, V>>, enode: &mut Node<K, V>) -> () {
+    // fails only if mem is full; the enode then stays in place, lookups still go through its
+    // narrow array, and the next insert that runs into it tries again
+    fn _complete_expansion(mem: &A, enode: &mut Node<K, V>) -> Result<(), AllocError> {
         //if we don't have an ENode, panic!
         //make refs to parent, narrow, and wide
         //parentpos and level don't need refs, because they're primitive
         //if let Node::ENode { ref parent, parentpos, ref narrow, level, wide: ref mut _wide, .. } = enode {
-        if node_type_eq(Node::ENode {
-            parent: AtomicPtr::new(mem.alloc(Node::ANode(makeanode(4)))), //parent array node
-            parentpos: 1 as u8, //position in parent array node
-            narrow: AtomicPtr::new(mem.alloc(Node::ANode(makeanode(4)))), //narrow version of enode (currently populated)
-            hash: 1 as u64,
-            level: 1 as u8,
-            wide: AtomicPtr::new(null_mut()), //wide version of enode (not yet populated)
-        }, enode) {
+        if is_enode(enode) {
+            let enodeptr = enode as *mut Node<K, V>;
             //let narrowptr = narrow.load(Ordering::Relaxed); //ptr to narrow array
             let narrowptr = get_narrowptr(enode);
-            LockfreeTrie::_freeze(mem, unsafe { &mut *narrowptr });//freeze narrow (make sure we can proceed)
-            let mut widenode = mem.alloc(Node::ANode(makeanode(16))); //make an ANode with 16 elements
+            LockfreeTrie::_freeze(mem, unsafe { &mut *narrowptr })?;//freeze narrow (make sure we can proceed)
+            let mut widenode = mem.try_alloc(Node::ANode(makeanode(16)))?; //make an ANode with 16 elements
             let level = get_enode_level(enode);
             if let Node::ANode(ref an) = unsafe { &*narrowptr } { //make ref to narrow array
                 //LockfreeTrie::_copy(mem, an, unsafe { &mut *widenode }, *level as u64); //copy narrow elements into widearray
-                LockfreeTrie::_copy(mem, an, unsafe { &mut *widenode }, level as u64);
+                if let Err(e) = LockfreeTrie::_copy(mem, an, unsafe { &mut *(widenode as *mut Node<K, V>) }, level as u64) {
+                    LockfreeTrie::_release(mem, widenode); //never published
+                    return Err(e);
+                }//if
             } else {
                 // this has never happened once, but just to be sure...
                 panic!("CORRUPTION: narrow is not an ANode")
@@ -477,6 +730,8 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
             //switch to the wide array
             //if _wide.compare_and_swap(null_mut(), widenode, Ordering::Relaxed) != null_mut() {
             if get_enode__wide(enode).compare_and_swap(null_mut(), widenode, Ordering::Relaxed) != null_mut() {
+                //another thread published its copy first, ours was never seen by anyone
+                LockfreeTrie::_release(mem, widenode);
                 //let _wideptr = _wide.load(Ordering::Relaxed);
                 let _wideptr = get_enode__wide(enode).load(Ordering::Relaxed);
                 if let Node::ANode(ref an) = unsafe { &mut *_wideptr } {
@@ -490,7 +745,7 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
             //let parentref = get_enode_parentref(enode);
             //let parentpos = get_enode_parentpos(enode);
             //if let Node::ANode(ref an) = parentref { //set ref to parent
-            if node_type_eq(Node::ANode(makeanode(4)), get_enode_parentref(enode)) {
+            if is_anode(get_enode_parentref(enode)) {
                 //let anptr = &an[*parentpos as usize];
                 //check to make sure that the parent's array points to the enode then replace it with the widenode
                 //anptr.compare_and_swap(enode, widenode, Ordering::Relaxed);
@@ -500,8 +755,12 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
                 //}
                 //anptr.compare_and_swap(enode, widenode, Ordering::Relaxed);
                 //get_enode_anptr(enode).compare_and_swap(enode, widenode, Ordering::Relaxed);
-                let anptr = get_enode_anptr(enode);       //doesn't do check to make sure that anptr currently
-                anptr.store(widenode, Ordering::Relaxed); //points to enode before storing a ptr to widenode
+                let anptr = get_enode_anptr(enode);
+                //only the thread that swaps the enode out of the parent retires the frozen narrow array
+                if anptr.compare_and_swap(enodeptr, widenode, Ordering::Relaxed) == enodeptr {
+                    LockfreeTrie::_retire_frozen(mem, narrowptr);
+                    mem.retire(unsafe { &mut *enodeptr });
+                }//if
                 //let anptr = {
                 //    let an = get_enode_an(enode);
                 //    &an[*(get_enode_parentpos(enode)) as usize]
@@ -526,46 +785,113 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
             // this has never happened once, but just to be sure...
             panic!("CORRUPTION: enode is not an ENode")
         }//if-else
+        Ok(())
     }//_complete_expansion
 
+    //_release: free a node that was never published, along with everything it points to
+    fn _release(mem: &A, nodeptr: *mut Node<K, V>) -> () {
+        match unsafe { &*nodeptr } {
+            Node::ANode(ref an) => {
+                for node in an {
+                    let childptr = node.load(Ordering::Relaxed);
+                    if !childptr.is_null() {
+                        LockfreeTrie::_release(mem, childptr);
+                    }//if
+                }//for
+            }//ANode
+            Node::SNode { ref txn, .. } => {
+                mem.dealloc(unsafe { &mut *txn.load(Ordering::Relaxed) });
+            }//SNode
+            _ => { /* nothing else to free */ }
+        }//match
+        mem.dealloc(unsafe { &mut *nodeptr });
+    }//_release
+
+    //_retire_frozen: retire a frozen narrow ANode that has been replaced by its wide copy,
+    //  along with the nodes and markers that only it points to
+    fn _retire_frozen(mem: &A, nodeptr: *mut Node<K, V>) -> () {
+        if let Node::ANode(ref an) = unsafe { &*nodeptr } {
+            for node in an {
+                let childptr = node.load(Ordering::Relaxed);
+                if childptr.is_null() {
+                    continue;
+                }//if
+                match unsafe { &*childptr } {
+                    Node::SNode { ref txn, .. } => { //its txn is the FSNode marker
+                        mem.retire(unsafe { &mut *txn.load(Ordering::Relaxed) });
+                    }//SNode
+                    Node::FNode { ref frozen } => {
+                        LockfreeTrie::_retire_frozen(mem, frozen.load(Ordering::Relaxed));
+                    }//FNode
+                    _ => { /* FVNode */ }
+                }//match
+                mem.retire(unsafe { &mut *childptr });
+            }//for
+        }//if
+        mem.retire(unsafe { &mut *nodeptr });
+    }//_retire_frozen
+
+    //_new_snode: allocate an SNode along with its NoTxn marker
+    fn _new_snode(mem: &A, h: u64, key: K, val: V) -> Result<*mut Node<K, V>, AllocError> {
+        let txn: *mut Node<K, V> = mem.try_alloc(Node::NoTxn)?;
+        match mem.try_alloc(Node::SNode { hash: h, key: key, val: val, txn: AtomicPtr::new(txn) }) {
+            Ok(sn) => Ok(sn),
+            Err(e) => {
+                mem.dealloc(unsafe { &mut *txn });
+                Err(e)
+            }
+        }//match
+    }//_new_snode
+
     //_create_anode: if we already have data at an index,
     //               make an ANode with length 4 and hash both nodes into it
     // old: SNode already hashed to index
     // sn: new SNode that we want to insert
     // lev: level of the trie (used to determine which bits to use)
-    fn _create_anode(mem: &Allocator<Node<K, V>>, old: Node<K, V>, sn: Node<K, V>, lev: u8) -> ANode<K, V> {
+    // if mem is full, the arrays allocated here are freed again; old and sn are left to the caller
+    fn _create_anode(mem: &A, old: *mut Node<K, V>, sn: *mut Node<K, V>, lev: u8) -> Result<ANode<K, V>, AllocError> {
         let mut v = makeanode(4);
 
-        if let Node::SNode { hash: h_old, .. } = old { //ref to hash in SNode
+        if let (&Node::SNode { hash: h_old, .. }, &Node::SNode { hash: h_sn, .. }) = unsafe { (&*old, &*sn) } {
+            // if the two hashcodes only differ in the upper 2 bits of this level, a narrow
+            // array can never tell them apart; split them with a wide array right here
+            if (h_old >> lev) & 0xf != (h_sn >> lev) & 0xf && (h_old >> lev) & 0x3 == (h_sn >> lev) & 0x3 {
+                v = makeanode(16);
+            }//if
             let old_pos = (h_old >> lev) as usize & (v.len() - 1); //only use 2 bits associated with lev
-            if let Node::SNode { hash: h_sn, .. } = sn { //ref to hash in SNode
-                let sn_pos = (h_sn >> lev) as usize & (v.len() - 1); //only use 2 bits associated with lev
-                if old_pos == sn_pos {
-                    v[old_pos] = AtomicPtr::new(mem.alloc(Node::ANode(LockfreeTrie::_create_anode(mem, old, sn, lev + 4))));
-                } else {
-                    v[old_pos] = AtomicPtr::new(mem.alloc(old));
-                    v[sn_pos] = AtomicPtr::new(mem.alloc(sn));
-                }//if-else
+            let sn_pos = (h_sn >> lev) as usize & (v.len() - 1); //only use 2 bits associated with lev
+            if old_pos == sn_pos {
+                //take the slot first, so that a failure below leaves nothing else to undo
+                let subnode = mem.try_alloc(Node::FVNode)?;
+                match LockfreeTrie::_create_anode(mem, old, sn, lev + 4) {
+                    Ok(sub) => { *subnode = Node::ANode(sub); }
+                    Err(e) => {
+                        mem.dealloc(subnode);
+                        return Err(e);
+                    }
+                }//match
+                v[old_pos] = AtomicPtr::new(subnode);
             } else {
-                // this has never happened once, but just to be sure...
-                panic!("CORRUPTION: expected SNode");
-            }
-        } else {//if-else
+                v[old_pos] = AtomicPtr::new(old);
+                v[sn_pos] = AtomicPtr::new(sn);
+            }//if-else
+        } else {
             // this has never happened once, but just to be sure...
             panic!("CORRUPTION: expected SNode");
         }//if-else
-        return v;
+        Ok(v)
     }//_create_anode
 
 
 
     //_insert: insert a node into the hamt with value V at key K with allocator mem
-    fn _insert(mem: &Allocator<Node<K, V>>, //memory allocator
+    fn _insert(mem: &A, //memory allocator
                key: K, val: V, h: u64, lev: u8, //hash key, value, code, and level
                cur: &mut Node<K, V>, //current node (ANode)
-               prev: Option<&mut Node<K, V>>) -> bool { //previous node
+               prev: Option<&mut Node<K, V>>) -> Result<bool, AllocError> { //previous node
 
-        if node_type_eq(Node::ANode(makeanode(4)), cur) { //if the node is an ANode
+        mem.validate(cur);
+        if is_anode(cur) { //if the node is an ANode
         //if let Node::ANode(ref mut cur2) = cur { //ref to ANode in enum of ANode
 
             let pos = (h >> lev) as usize & (get_ary_length(cur) - 1);
@@ -577,21 +903,17 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
             //if oldptr.is_null() { //if there isn't a node at the current pos
             if is_oldptr_null(cur, pos) {
             //define an SNode
-                let sn = mem.alloc(Node::SNode {
-                    hash: h,
-                    key: key,
-                    val: val,
-                    txn: AtomicPtr::new(mem.alloc(Node::NoTxn)),
-                });
+                let sn = LockfreeTrie::_new_snode(mem, h, key, val)?;
                 //update oldptr
                 //if old.compare_and_swap(oldptr, sn, Ordering::Relaxed) == oldptr {
-                if get_old(cur, pos).compare_and_swap(get_oldptr(cur, pos), sn, Ordering::Relaxed) == get_oldptr(cur, pos) {
-                    true
+                if get_old(cur, pos).compare_and_swap(null_mut(), sn, Ordering::Relaxed) == null_mut() {
+                    Ok(true)
                 } else {
+                    LockfreeTrie::_release(mem, sn); //never published
                     LockfreeTrie::_insert(mem, key, val, h, lev, cur, prev)
                 }//if-else
             //} else if let Node::ANode(ref mut an) = oldref { //if we have an ANode
-            } else if node_type_eq(Node::ANode(makeanode(4)), get_oldref(cur, pos)) { //if the node is an ANode
+            } else if is_anode(get_oldref(cur, pos)) { //if the node is an ANode
                 let oldref = {
                     let oldptr = get_oldptr(cur, pos);
                     unsafe { &mut *oldptr }
@@ -599,7 +921,8 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
                 //LockfreeTrie::_insert(mem, key, val, h, lev + 4, get_oldref(cur, pos), Some(cur))
                 LockfreeTrie::_insert(mem, key, val, h, lev + 4, oldref, Some(cur))
             //} else if let Node::SNode { hash: _hash, key: _key, val: _val, ref mut txn } = get_oldref(cur, pos) { //if we have an SNode
-            } else if node_type_eq(Node::SNode{ hash: 1 as u64, key: key, val: val, txn: AtomicPtr::new(mem.alloc(Node::NoTxn))}, get_oldref(cur, pos)) {
+            } else if is_snode(get_oldref(cur, pos)) {
+                let oldptr = get_oldptr(cur, pos);
                 //let txnptr = txn.load(Ordering::Relaxed);
                 //let txnref = unsafe { &*txnptr };
                 let txnptr = {
@@ -611,41 +934,40 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
                 if let Node::NoTxn = txnref { //if the SNode has NoTxn
                     //if *_key == key { //if the insert key and key at this index match
                     if *(get_key(cur, pos)) == key {
-                        let sn = mem.alloc(Node::SNode { //make a new SNode
-                            hash: h,
-                            key: key,
-                            val: val,
-                            txn: AtomicPtr::new(mem.alloc(Node::NoTxn)),
-                        });
+                        let sn = LockfreeTrie::_new_snode(mem, h, key, val)?; //make a new SNode
                         //if txn.compare_and_swap(txnptr, sn, Ordering::Relaxed) == txnptr {
                         if get_txn(cur, pos).compare_and_swap(txnptr, sn, Ordering::Relaxed) == txnptr {
-                            get_old(cur, pos).compare_and_swap(get_oldptr(cur, pos), sn, Ordering::Relaxed);
-                            true
+                            get_old(cur, pos).compare_and_swap(oldptr, sn, Ordering::Relaxed);
+                            //the old SNode and its NoTxn marker are unlinked now
+                            mem.retire(unsafe { &mut *txnptr });
+                            mem.retire(unsafe { &mut *oldptr });
+                            Ok(true)
                         } else {
+                            LockfreeTrie::_release(mem, sn); //never published
                             LockfreeTrie::_insert(mem, key, val, h, lev, cur, prev)
                         }
                     } else if get_ary_length(cur) == 4 { //if we have a narrow array (might need to expand)
                         if let Some(prevref) = prev {
                             //if let Node::ANode(ref mut prev2) = prevref {
-                            if node_type_eq(Node::ANode(makeanode(4)), prevref) {
+                            if is_anode(prevref) {
                                 //let ppos = (h >> (lev - 4)) as usize & (prev2.len() - 1);
                                 let ppos = (h >> (lev - 4)) as usize & (get_ary_length(prevref) - 1);
                                 //let prev2aptr = &prev2[ppos];
-                                let en = mem.alloc(Node::ENode {
+                                let en = mem.try_alloc(Node::ENode {
                                     parent: AtomicPtr::new(prevref),
                                     parentpos: ppos as u8,
                                     narrow: AtomicPtr::new(cur),
                                     hash: h,
                                     level: lev,
                                     wide: AtomicPtr::new(null_mut()),
-                                });
+                                })?;
                                 //let en: Node::ENode;
                                 //if prev2aptr.compare_and_swap(cur, en, Ordering::Relaxed) == cur {
                                 //determine if prev2aptr contains ptr to cur
                                 //swap ptr to en if that's true and continue in if-statement
                                 if get_prev2aptr(prevref, ppos).compare_and_swap(cur, en, Ordering::Relaxed) == cur {
                                 //if CAS_check_eq(get_prev2aptr(prevref, ppos), cur, en) {
-                                    LockfreeTrie::_complete_expansion(mem, unsafe { &mut *en });
+                                    LockfreeTrie::_complete_expansion(mem, unsafe { &mut *(en as *mut Node<K, V>) })?;
                                     if let Node::ENode { ref wide, .. } = unsafe { &mut *en } {
                                         let wideref = unsafe { &mut *wide.load(Ordering::Relaxed) };
                                         LockfreeTrie::_insert(mem, key, val, h, lev, wideref, Some(prevref))
@@ -654,6 +976,7 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
                                         panic!("CORRUPTION: en is not an ENode")
                                     }
                                 } else {
+                                    mem.dealloc(en); //never published
                                     LockfreeTrie::_insert(mem, key, val, h, lev, cur, Some(prevref))
                                 }
                             } else {
@@ -665,38 +988,56 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
                             panic!("ERROR: prev is None")
                         }
                     } else { //if we don't have an array, create one
-                        let an = mem.alloc(Node::ANode(LockfreeTrie::_create_anode(mem,
-                                                                                   Node::SNode {
-                                                                                       hash: *(get_hash(cur, pos)),
-                                                                                       key: *(get_key(cur, pos)),
-                                                                                       val: *(get_val(cur, pos)),
-                                                                                       txn: AtomicPtr::new(mem.alloc(Node::NoTxn)),
-                                                                                   },
-                                                                                   Node::SNode {
-                                                                                       hash: h,
-                                                                                       key: key,
-                                                                                       val: val,
-                                                                                       txn: AtomicPtr::new(mem.alloc(Node::NoTxn)),
-                                                                                   }, lev + 4)));
+                        //copy the old SNode, and take every slot before building the array,
+                        //so that running out of memory leaves nothing half-built behind
+                        let oldsn = LockfreeTrie::_new_snode(mem, *(get_hash(cur, pos)), *(get_key(cur, pos)), *(get_val(cur, pos)))?;
+                        let sn = match LockfreeTrie::_new_snode(mem, h, key, val) {
+                            Ok(sn) => sn,
+                            Err(e) => {
+                                LockfreeTrie::_release(mem, oldsn);
+                                return Err(e);
+                            }
+                        };//match
+                        let an: *mut Node<K, V> = match mem.try_alloc(Node::FVNode) {
+                            Ok(an) => an,
+                            Err(e) => {
+                                LockfreeTrie::_release(mem, oldsn);
+                                LockfreeTrie::_release(mem, sn);
+                                return Err(e);
+                            }
+                        };//match
+                        match LockfreeTrie::_create_anode(mem, oldsn, sn, lev + 4) {
+                            Ok(v) => unsafe { *an = Node::ANode(v); },
+                            Err(e) => {
+                                mem.dealloc(unsafe { &mut *an });
+                                LockfreeTrie::_release(mem, oldsn);
+                                LockfreeTrie::_release(mem, sn);
+                                return Err(e);
+                            }
+                        }//match
                         //if txn.compare_and_swap(txnptr, an, Ordering::Relaxed) == txnptr {
                         if get_txn(cur, pos).compare_and_swap(txnptr, an, Ordering::Relaxed) == txnptr {
-                            get_old(cur, pos).compare_and_swap(get_oldptr(cur, pos), an, Ordering::Relaxed);
-                            true
+                            get_old(cur, pos).compare_and_swap(oldptr, an, Ordering::Relaxed);
+                            //the old SNode was copied into the new array
+                            mem.retire(unsafe { &mut *txnptr });
+                            mem.retire(unsafe { &mut *oldptr });
+                            Ok(true)
                         } else {
+                            LockfreeTrie::_release(mem, an); //never published
                             LockfreeTrie::_insert(mem, key, val, h, lev, cur, prev)
                         }
                     }
                 } else if let Node::FSNode = txnref {
-                    false
+                    Ok(false)
                 } else {
                     get_old(cur, pos).compare_and_swap(get_oldptr(cur, pos), txnptr, Ordering::Relaxed);
                     LockfreeTrie::_insert(mem, key, val, h, lev, cur, prev)
                 }
             } else { //otherwise
                 if let Node::ENode { .. } = get_oldref(cur, pos) {
-                    LockfreeTrie::_complete_expansion(mem, get_oldref(cur, pos));
+                    LockfreeTrie::_complete_expansion(mem, get_oldref(cur, pos))?;
                 }
-                false
+                Ok(false)
             }
         } else {
             // this has never happened once, but just to be sure...
@@ -704,12 +1045,29 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
         }
     }//_insert
 
-    //insert: call the _insert function
+    //insert: call the _insert function until it succeeds; panics if the allocator is full
     pub fn insert(&mut self, key: K, val: V) -> bool {
-        LockfreeTrie::_insert(&mut self.mem, key, val, hash(key), 0, unsafe { &mut *self.root.load(Ordering::Relaxed) }, None)
-            || self.insert(key, val)
+        self.try_insert(key, val).expect("LockfreeTrie::insert");
+        true
     }//insert
 
+    //try_insert: insert, or report CapacityExceeded if the allocator runs out of slots
+    // the trie stays consistent: the key is either inserted or not, and any expansion that
+    // was cut short is finished by a later insert
+    pub fn try_insert(&mut self, key: K, val: V) -> Result<(), AllocError> {
+        let h = (self.hasher)(&key);
+        let result = loop {
+            match LockfreeTrie::_insert(&self.mem, key, val, h, 0, unsafe { &mut *self.root.load(Ordering::Relaxed) }, None) {
+                Ok(true) => break Ok(()),
+                Ok(false) => continue,
+                Err(e) => break Err(e),
+            }//match
+        };//loop
+        //insert has the only reference to the trie, so no lookup can still be reading the nodes it replaced
+        self.mem.reclaim();
+        result
+    }//try_insert
+
     //_inhabit:
     fn _inhabit<'a>(&'a self,
                     cache: Option<&'a CacheLevel<K, V>>, //CacheLevel
@@ -810,7 +1168,7 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
                         }//if
                         hist[level as usize] += 1; //add one to cache level
                     } else if let Node::ANode(_) = vref { // if its an ANode, fill hist for that level
-                        LockfreeTrie::_fill_hist(hist, vref, level + 1);
+                        Self::_fill_hist(hist, vref, level + 1);
                     }//if-else
                 }//if
             }//for
@@ -822,7 +1180,7 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
         let mut hist = Vec::new();
 
         let root = unsafe { &*self.root.load(Ordering::Relaxed) };
-        LockfreeTrie::_fill_hist(&mut hist, root, 0);
+        Self::_fill_hist(&mut hist, root, 0);
 
         hist
     }//_sample_snodes_levels
@@ -831,13 +1189,14 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
     fn _lookup<'a>(&self, key: &K, h: u64, lev: u8, cur: &'a mut Node<K, V>,
                    cache: Option<&'a CacheLevel<K, V>>, cache_lev: Option<u8>) -> Option<&'a V> {
 
+        self.mem.validate(cur);
         //if let Node::ANode(ref cur2) = cur { //if cur is of enum type ANode, make reference to array node
-        if node_type_eq(Node::ANode(makeanode(4)), cur) {
+        if is_anode(cur) {
             if Some(lev) == cache_lev { //if cache_lev contains something
                 self._inhabit(cache, cur, h, lev);
             }//if
 
-            let cur2:Vec<AtomicPtr<Node<K, V>>>;
+            let cur2: ANode<K, V>;
             /*
             match cur {
                 Node::ANode(ref cur2) => {  }, // maybe put everything in here?
@@ -849,16 +1208,18 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
                 Node::ANode(ref cur2) => {
                     let pos = (h >> lev) as usize & (cur2.len() - 1); //index for level
                     let oldptr = (&cur2[pos]).load(Ordering::Relaxed);
+                    if oldptr.is_null() { //if there isn't anything at pos
+                        return None;
+                    }//if
                     let oldref = unsafe { &mut *oldptr };
 
-                    if oldptr.is_null() { //if there isn't anything at pos
-                        None
-                    } else if let Node::FVNode = oldref { //if oldref refs to an empty frozen array node
+                    if let Node::FVNode = oldref { //if oldref refs to an empty frozen array node
                         None
                     //} else if let Node::ANode(ref an) = oldref {  //if it refs to an ANode
-                    } else if node_type_eq(Node::ANode(makeanode(4)), oldref) { //if the node is an ANode
+                    } else if is_anode(oldref) { //if the node is an ANode
                         self._lookup(key, h, lev + 4, oldref, cache, cache_lev) //look further down the trie
                     } else if let Node::SNode { key: _key, val, .. } = oldref { //if it contains data
+                        self.mem.validate(unsafe { &*oldptr });
                         if let Some(clev) = cache_lev {
                             if !(lev >= clev || lev <= clev + 4) {
                                 self._record_miss();
@@ -907,7 +1268,7 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
             } else if let Node::FVNode = oldref { //if oldref refs to an empty frozen array node
                 None
             //} else if let Node::ANode(ref an) = oldref {  //if it refs to an ANode
-            } else if node_type_eq(Node::ANode(makeanode(4)), oldref) { //if the node is an ANode
+            } else if is_anode(oldref) { //if the node is an ANode
                 self._lookup(key, h, lev + 4, oldref, cache, cache_lev) //look further down the trie
             } else if let Node::SNode { key: _key, val, .. } = oldref { //if it contains data
                 if let Some(clev) = cache_lev {
@@ -942,11 +1303,14 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
      * implemented as fastLookup()
      */
     pub fn lookup(&self, key: &K) -> Option<&V> {
-        let h = hash(key);
+        let h = (self.hasher)(key);
+        if self.profiling.load(Ordering::Relaxed) && LOOKUPS.with(|n| { n.set(n.get().wrapping_add(1)); n.get() % SAMPLE_PERIOD == 0 }) {
+            self._sample(h);
+        }//if
         let mut cache_head_ptr = self.cache.load(Ordering::Relaxed);
 
         if cache_head_ptr.is_null() {
-            self._lookup(key, hash(key), 0, unsafe { &mut *self.root.load(Ordering::Relaxed) }, None, None)
+            self._lookup(key, h, 0, unsafe { &mut *self.root.load(Ordering::Relaxed) }, None, None)
         } else {
             let cache_head = unsafe { &*cache_head_ptr };
             let top_level = (cache_head.nodes.capacity() - 1).trailing_zeros();
@@ -966,7 +1330,7 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
                             }
                         }
                     } else if let Node::ANode(ref an) = cachee {
-                        let cpos = (h >> level) & (an.capacity() - 1) as u64;
+                        let cpos = (h >> level) & (an.len() - 1) as u64;
                         let oldptr = an[cpos as usize].load(Ordering::Relaxed);
 
                         if !oldptr.is_null() {
@@ -974,12 +1338,389 @@ impl<K: TrieKey, V: TrieData> LockfreeTrie<K, V> {
                                 if let Node::FSNode = unsafe { &*txn.load(Ordering::Relaxed) } { continue; }
                             }
                         }
-                        return self._lookup(key, hash(key), 0, unsafe { &mut *self.root.load(Ordering::Relaxed) }, Some(cache_head), Some(level as u8));
+                        return self._lookup(key, h, 0, unsafe { &mut *self.root.load(Ordering::Relaxed) }, Some(cache_head), Some(level as u8));
                     }
                 }
                 cache_head_ptr = cache_head.parent.load(Ordering::Relaxed);
             }
-            self._lookup(key, hash(key), 0, unsafe { &mut *self.root.load(Ordering::Relaxed) }, None, Some(top_level as u8))
+            self._lookup(key, h, 0, unsafe { &mut *self.root.load(Ordering::Relaxed) }, None, Some(top_level as u8))
         }
     }
-}
+
+    //start_profiling: sample the paths of lookups from now on, so that repack knows which
+    //  nodes are hot
+    // off by default: a sampled lookup counts its visits on counters every thread writes to
+    pub fn start_profiling(&self) -> () {
+        self.profiling.store(true, Ordering::Relaxed);
+    }//start_profiling
+
+    //stop_profiling: stop sampling lookups; the visits counted so far are kept for repack
+    pub fn stop_profiling(&self) -> () {
+        self.profiling.store(false, Ordering::Relaxed);
+    }//stop_profiling
+
+    //_heat: visit counter of the node at nodeptr
+    fn _heat(&self, nodeptr: *const Node<K, V>) -> &AtomicU32 {
+        &self.heat[(nodeptr as usize / mem::size_of::<Node<K, V>>()) % HEAT_SLOTS]
+    }//_heat
+
+    //_sample: count a visit on every node of the path a lookup of h takes
+    fn _sample(&self, h: u64) -> () {
+        let mut nodeptr = self.root.load(Ordering::Relaxed);
+        let mut lev = 0;
+        while !nodeptr.is_null() {
+            self._heat(nodeptr).fetch_add(1, Ordering::Relaxed);
+            match unsafe { &*nodeptr } {
+                Node::ANode(ref an) => {
+                    nodeptr = an[(h >> lev) as usize & (an.len() - 1)].load(Ordering::Relaxed);
+                    lev += 4;
+                }//ANode
+                Node::ENode { ref narrow, .. } => { nodeptr = narrow.load(Ordering::Relaxed); }
+                Node::FNode { ref frozen } => { nodeptr = frozen.load(Ordering::Relaxed); }
+                _ => { break; }
+            }//match
+        }//while
+    }//_sample
+
+    //_select_hot: append the hot nodes under nodeptr to hot in depth-first order, hottest child first
+    // only ANodes and SNodes without a pending transaction are moved. An ANode holding an ENode
+    // or FNode stays where it is, with everything under it: the ENode names it as its parent,
+    // and the insert that finishes the expansion swaps the wide ANode into that parent
+    fn _select_hot(&self, nodeptr: *mut Node<K, V>, threshold: u32, budget: usize, hot: &mut Vec<*mut Node<K, V>>) -> () {
+        if hot.len() >= budget || self._heat(nodeptr).load(Ordering::Relaxed) < threshold {
+            return;
+        }//if
+        match unsafe { &*nodeptr } {
+            Node::ANode(ref an) => {
+                let pending = an.iter().any(|node| {
+                    let childptr = node.load(Ordering::Relaxed);
+                    !childptr.is_null() && match unsafe { &*childptr } {
+                        Node::ENode { .. } | Node::FNode { .. } => true,
+                        _ => false,
+                    }//match
+                });
+                if pending {
+                    return;
+                }//if
+                hot.push(nodeptr);
+                let mut children: Vec<(u32, *mut Node<K, V>)> = an.iter()
+                    .map(|node| node.load(Ordering::Relaxed))
+                    .filter(|childptr| !childptr.is_null())
+                    .map(|childptr| (self._heat(childptr).load(Ordering::Relaxed), childptr))
+                    .collect();
+                children.sort_by(|a, b| b.0.cmp(&a.0));
+                for (_, childptr) in children {
+                    self._select_hot(childptr, threshold, budget, hot);
+                }//for
+            }//ANode
+            Node::SNode { ref txn, .. } => {
+                if let Node::NoTxn = unsafe { &*txn.load(Ordering::Relaxed) } {
+                    hot.push(nodeptr);
+                }//if
+            }//SNode
+            _ => { /* in the middle of an insert, leave it where it is */ }
+        }//match
+    }//_select_hot
+
+    //repack: dynamic data packing, move the nodes most sampled lookups went through into one
+    //  contiguous region of the arena
+    // only lookups made while profiling are sampled, see start_profiling
+    // the hot nodes are copied in depth-first order, hottest child first, so a hot lookup path
+    // touches as few cache lines as possible. The copy is published with a CAS on the root; the
+    // old nodes are retired and their slots are recycled by the next insert. Lookups can run
+    // alongside. Returns the number of nodes moved, at most budget.
+    pub fn repack(&self, budget: usize) -> usize {
+        let _guard = self.repacking.lock().unwrap();
+        let rootptr = self.root.load(Ordering::Relaxed);
+        let samples = self._heat(rootptr).load(Ordering::Relaxed);
+        if samples == 0 {
+            return 0;
+        }//if
+
+        let mut hot = Vec::new();
+        let threshold = if samples / HOT_RATIO > 0 { samples / HOT_RATIO } else { 1 };
+        self._select_hot(rootptr, threshold, budget, &mut hot);
+        if hot.is_empty() {
+            //the root holds a pending expansion
+            return 0;
+        }//if
+        let mut region: Region<Node<K, V>> = match self.mem.region(hot.len()) {
+            Some(region) => region,
+            None => return 0,
+        };//match
+
+        //copy the nodes, children pointers still point to the old nodes
+        let mut moved = HashMap::new();
+        for &oldptr in hot.iter() {
+            let copy: *mut Node<K, V> = match unsafe { &*oldptr } {
+                Node::ANode(ref an) => {
+                    let an2 = makeanode(an.len());
+                    for pos in 0..an.len() {
+                        an2[pos].store(an[pos].load(Ordering::Relaxed), Ordering::Relaxed);
+                    }//for
+                    region.alloc(Node::ANode(an2))
+                }//ANode
+                //the txn marker is shared with the copy; the old SNode is unreachable once the copy is published
+                Node::SNode { hash, key, val, txn } => {
+                    region.alloc(Node::SNode { hash: *hash, key: *key, val: *val, txn: AtomicPtr::new(txn.load(Ordering::Relaxed)) })
+                }//SNode
+                _ => { panic!("CORRUPTION: only ANodes and SNodes are repacked") }
+            };//match
+            moved.insert(oldptr, copy);
+        }//for
+
+        //link the copies to each other
+        for copy in moved.values() {
+            if let Node::ANode(ref an) = unsafe { &**copy } {
+                for node in an {
+                    if let Some(&childcopy) = moved.get(&node.load(Ordering::Relaxed)) {
+                        node.store(childcopy, Ordering::Relaxed);
+                    }//if
+                }//for
+            }//if
+        }//for
+
+        if self.root.compare_and_swap(rootptr, moved[&rootptr], Ordering::Relaxed) == rootptr {
+            for &oldptr in hot.iter() {
+                self.mem.retire(unsafe { &mut *oldptr });
+            }//for
+        } else {
+            //the copies share their children with the old nodes, so only free the copies themselves
+            for copy in moved.values() {
+                self.mem.dealloc(unsafe { &mut **copy });
+            }//for
+            return 0;
+        }//if-else
+
+        //the counters were indexed by the old addresses
+        for counter in self.heat.iter() {
+            counter.store(0, Ordering::Relaxed);
+        }//for
+        hot.len()
+    }//repack
+
+    //_count_reachable: count the nodes under nodeptr, including the txn markers of its SNodes
+    fn _count_reachable(nodeptr: *mut Node<K, V>, counts: &mut NodeCounts) -> () {
+        if nodeptr.is_null() {
+            return;
+        }//if
+        let node = unsafe { &*nodeptr };
+        counts.count(node);
+        match *node {
+            Node::ANode(ref an) => {
+                for child in an {
+                    Self::_count_reachable(child.load(Ordering::Relaxed), counts);
+                }//for
+            }//ANode
+            Node::SNode { ref txn, .. } => { Self::_count_reachable(txn.load(Ordering::Relaxed), counts); }
+            Node::FNode { ref frozen } => { Self::_count_reachable(frozen.load(Ordering::Relaxed), counts); }
+            Node::ENode { ref narrow, ref wide, .. } => {
+                Self::_count_reachable(narrow.load(Ordering::Relaxed), counts);
+                Self::_count_reachable(wide.load(Ordering::Relaxed), counts);
+            }//ENode
+            _ => { /* markers point to nothing */ }
+        }//match
+    }//_count_reachable
+
+    //memory_usage: how much memory the trie holds, and which nodes it is spent on
+    // walks every node, so it is meant for sizing and debugging rather than hot paths
+    pub fn memory_usage(&self) -> MemoryUsage {
+        //inserts need &mut self, so only a repack could allocate while we count
+        let _guard = self.repacking.lock().unwrap();
+        let mut usage = MemoryUsage::default();
+        usage.allocator = self.mem.usage();
+        unsafe { self.mem.for_each_live(|node| usage.live.count(node)); }
+        Self::_count_reachable(self.root.load(Ordering::Relaxed), &mut usage.reachable);
+        usage
+    }//memory_usage
+
+    //get_many: look up a batch of keys, returning the values in the same order
+    // the traversals are interleaved in groups: every round advances each unfinished lookup by
+    // one pointer dereference and prefetches what it will read in the next round (the node it
+    // moves to, then the slot of that ANode it will follow), so the cache misses of the keys in
+    // a group overlap instead of stalling one after another as they do in lookup
+    pub fn get_many(&self, keys: &[K]) -> Vec<Option<V>> {
+        let mut result = vec![None; keys.len()];
+        let root = self.root.load(Ordering::Relaxed) as *const Node<K, V>;
+        prefetch(root);
+
+        let mut group_start = 0;
+        while group_start < keys.len() {
+            let group_end = if group_start + PREFETCH_GROUP < keys.len() { group_start + PREFETCH_GROUP } else { keys.len() };
+            let mut probes: Vec<Probe<K, V>> = keys[group_start..group_end].iter().map(|key| Probe {
+                node: root,
+                slot: false,
+                h: (self.hasher)(key),
+                lev: 0,
+            }).collect();
+            let mut active = probes.len();
+
+            while active > 0 {
+                for (i, probe) in probes.iter_mut().enumerate() {
+                    if probe.node.is_null() {
+                        continue;
+                    }//if
+
+                    self.mem.validate(unsafe { &*probe.node });
+                    match unsafe { &*probe.node } {
+                        Node::ANode(ref an) => {
+                            let pos = (probe.h >> probe.lev) as usize & (an.len() - 1);
+                            if !probe.slot { //first visit: only prefetch the slot
+                                prefetch(&an[pos] as *const AtomicPtr<Node<K, V>>);
+                                probe.slot = true;
+                                continue;
+                            }//if
+                            let nextptr = an[pos].load(Ordering::Relaxed);
+                            probe.slot = false;
+                            probe.lev += 4;
+                            probe.node = nextptr;
+                            if nextptr.is_null() { //nothing at pos
+                                active -= 1;
+                            } else {
+                                prefetch(nextptr as *const Node<K, V>);
+                            }//if-else
+                        }//ANode
+                        Node::SNode { ref key, ref val, .. } => {
+                            if *key == keys[group_start + i] {
+                                result[group_start + i] = Some(*val);
+                            }//if
+                            probe.node = null_mut();
+                            active -= 1;
+                        }//SNode
+                        //the narrow or frozen array is at the level we already moved to
+                        Node::ENode { ref narrow, .. } => {
+                            probe.node = narrow.load(Ordering::Relaxed);
+                            prefetch(probe.node);
+                        }//ENode
+                        Node::FNode { ref frozen } => {
+                            probe.node = frozen.load(Ordering::Relaxed);
+                            prefetch(probe.node);
+                        }//FNode
+                        Node::FVNode => { //frozen empty slot
+                            probe.node = null_mut();
+                            active -= 1;
+                        }//FVNode
+                        _ => {
+                            // this has never happened once, but just to be sure...
+                            panic!("CORRUPTION: probe is not at a valid node")
+                        }
+                    }//match
+                }//for
+            }//while
+
+            group_start = group_end;
+        }//while
+
+        result
+    }//get_many
+
+    //_range: collect the SNodes under cur whose key bits lie in [lo, hi], in key order
+    // cur: an ANode at level lev
+    // fixed, mask: the key bits already decided by the path from the root to cur
+    //  (only meaningful in order-preserving mode, where the hashcode is the key's own bits)
+    fn _range(lo: u64, hi: u64, lev: u8, cur: &Node<K, V>, fixed: u64, mask: u64, out: &mut Vec<(u64, K, V)>) -> () {
+        if let Node::ANode(ref an) = cur {
+            //a wide array splits on all 4 bits of the level, a narrow one only on the lower 2
+            let width = if an.len() == 16 { 4 } else { 2 };
+            let shift = 60 - lev as u64;
+            let start = out.len();
+
+            for pos in 0..an.len() { //slot order is key order for a wide array
+                let nodeptr = an[pos].load(Ordering::Relaxed);
+                if nodeptr.is_null() {
+                    continue;
+                }//if
+
+                let child_mask = mask | (((1 << width) - 1) << shift);
+                let child_fixed = fixed | ((pos as u64) << shift);
+                //prune subtrees that can't hold a key in [lo, hi]
+                if child_fixed > hi || (child_fixed | !child_mask) < lo {
+                    continue;
+                }//if
+
+                match unsafe { &*nodeptr } {
+                    Node::SNode { hash, key, val, .. } => {
+                        let bits = reverse_nibbles(*hash);
+                        if lo <= bits && bits <= hi {
+                            out.push((bits, *key, *val));
+                        }//if
+                    }//SNode
+                    Node::ANode(_) => {
+                        Self::_range(lo, hi, lev + 4, unsafe { &*nodeptr }, child_fixed, child_mask, out);
+                    }//ANode
+                    //an expansion in progress: the narrow array is still the valid copy
+                    Node::ENode { ref narrow, .. } => {
+                        Self::_range(lo, hi, lev + 4, unsafe { &*narrow.load(Ordering::Relaxed) }, child_fixed, child_mask, out);
+                    }//ENode
+                    Node::FNode { ref frozen } => {
+                        Self::_range(lo, hi, lev + 4, unsafe { &*frozen.load(Ordering::Relaxed) }, child_fixed, child_mask, out);
+                    }//FNode
+                    _ => { /* FVNode: frozen empty slot */ }
+                }//match
+            }//for
+
+            //a narrow array leaves the upper 2 bits of the level to its subtrees, so the
+            //entries collected below it are only ordered once sorted
+            if width == 2 {
+                out[start..].sort_by_key(|entry| entry.0);
+            }//if
+        } else {
+            // this has never happened once, but just to be sure...
+            panic!("CORRUPTION: cur is not an ANode")
+        }//if-else
+    }//_range
+}//impl LockfreeTrie
+
+//order-preserving mode: only for integer keys
+impl<K: OrderedKey, V: TrieData> LockfreeTrie<K, V> {
+    //constructor
+    // keys are placed by their own bits instead of their hash, most significant bits first,
+    // so the trie is laid out in key order and supports range and iter_from
+    pub fn new_ordered() -> Self {
+        LockfreeTrie::ordered_with_allocator(Allocator::reserve(DEFAULT_CAPACITY))
+    }//new_ordered
+}//impl LockfreeTrie
+
+impl<K: OrderedKey, V: TrieData, A: NodeAllocator<Node<K, V>>> LockfreeTrie<K, V, A> {
+    //constructor: new_ordered with the nodes in mem
+    pub fn ordered_with_allocator(mem: A) -> Self {
+        let mut trie = LockfreeTrie::with_allocator(mem);
+        trie.hasher = ordered_hash::<K>;
+        trie.ordered = true;
+        trie
+    }//ordered_with_allocator
+
+    //range: iterate over the entries whose keys lie in range, in ascending key order
+    // only the subtrees overlapping the range are visited. The walk only reads the
+    // atomic slots, so it can run alongside inserts and in-progress expansions; entries
+    // inserted while it runs may or may not be returned.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> RangeIter<K, V> {
+        assert!(self.ordered, "range queries need a trie built with LockfreeTrie::new_ordered");
+        let mut entries = Vec::new();
+
+        let lo = match range.start_bound() {
+            Bound::Included(k) => Some(k.key_bits()),
+            Bound::Excluded(k) => k.key_bits().checked_add(1),
+            Bound::Unbounded => Some(0),
+        };//lo
+        let hi = match range.end_bound() {
+            Bound::Included(k) => Some(k.key_bits()),
+            Bound::Excluded(k) => k.key_bits().checked_sub(1),
+            Bound::Unbounded => Some(u64::max_value()),
+        };//hi
+
+        if let (Some(lo), Some(hi)) = (lo, hi) {
+            if lo <= hi {
+                let root = unsafe { &*self.root.load(Ordering::Relaxed) };
+                Self::_range(lo, hi, 0, root, 0, 0, &mut entries);
+            }//if
+        }//if
+
+        RangeIter { entries: entries.into_iter() }
+    }//range
+
+    //iter_from: iterate over the entries with keys >= key, in ascending key order
+    pub fn iter_from(&self, key: K) -> RangeIter<K, V> {
+        self.range(key..)
+    }//iter_from
+}//impl LockfreeTrie
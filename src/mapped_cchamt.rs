@@ -0,0 +1,371 @@
+/// Cache Conscious Trie saved to a file and mapped back read-only
+/// ContiguousTrie::save writes the blocks of the trie one level after the other, each level in key
+/// order, after a header, with every interior slot holding the index of its child block in the
+/// file, so MappedContiguousTrie::open only has to mmap the file and check it, no deserializing. Processes that open the same file share its pages in the page cache.
+
+// File layout, in the byte order of the machine that saved it:
+//
+//     header:  magic, version, byte order, key type width, key_length, levels, value and slot
+//              size, segment size of every level, number of entries and slots, checksum
+//     slots:   one Slot per slot of ContiguousTrie's blocks, the root block first
+//
+// The checksum covers the slots, and open rejects a file whose header doesn't match the key and
+// value types it is opened with.
+
+extern crate libc;
+use std::fs::File;
+use std::io::{self, Write, Seek, SeekFrom, BufWriter};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::slice;
+use cchamt::{PrimInt, TrieData};
+use error::TrieError;
+
+const MAGIC: [u8; 8] = *b"CCTRIE\0\0";
+const VERSION: u32 = 1;
+// reads back as another number on a machine of the other byte order
+const BYTE_ORDER: u32 = 0x0102_0304;
+const HEADER_SIZE: usize = 128;
+const MAX_LEVELS: usize = 64;
+
+// next of a slot that holds nothing
+const EMPTY: u64 = u64::max_value();
+// next of a leaf slot, its value is in value
+const LEAF: u64 = u64::max_value() - 1;
+
+/// Values that can be stored in a mapped file and read back by reinterpreting its bytes
+/// Unsafe to implement: the type must hold no pointers or padding, accept any bit pattern,
+/// and be aligned to at most 8 bytes.
+pub unsafe trait Pod: TrieData {}
+
+macro_rules! pod {
+    ($($t:ty),*) => {$(
+        unsafe impl Pod for $t {}
+    )*};
+}//pod
+
+pod!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+#[repr(C)]
+struct Header {
+    magic: [u8; 8],
+    version: u32,
+    byte_order: u32,
+    header_size: u32,
+    key_bits: u32, // width of the key type
+    key_length: u32,
+    levels: u32,
+    value_size: u32,
+    slot_size: u32,
+    segments: [u8; MAX_LEVELS], // segment size of each level, the rest is 0
+    len: u64, // number of entries
+    slots: u64,
+    checksum: u64,
+}//struct Header
+
+// a slot of a ContiguousTrie block as it is stored in the file
+// next is the start of the block below, EMPTY or LEAF
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Slot<V: Pod> {
+    next: u64,
+    value: V,
+}//struct Slot
+
+/// What a slot of a ContiguousTrie block holds, handed to write
+pub enum Entry<V> {
+    Empty,
+    Child(usize), // the start of the block below
+    Leaf(V),
+}//enum Entry
+
+// FNV-1a, continued from hash
+fn checksum(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100_0000_01b3);
+    }//for
+    hash
+}//checksum
+
+const CHECKSUM_SEED: u64 = 0xcbf2_9ce4_8422_2325;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}//invalid
+
+// write the header and the slots of a trie with the given key type width and segments to path
+pub fn write<V: Pod, I: Iterator<Item = Entry<V>>>(path: &Path, key_bits: usize, segments: &[usize], slots: I)
+                                                   -> io::Result<()> {
+    assert!(segments.len() <= MAX_LEVELS);
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&[0; HEADER_SIZE])?; // the header is written once the slots are counted
+
+    let slot_size = mem::size_of::<Slot<V>>();
+    // the padding of a slot is written as zeros, the value sits right after next
+    let mut bytes = vec![0u8; slot_size];
+    let mut hash = CHECKSUM_SEED;
+    let mut len = 0;
+    let mut nslots = 0;
+    for entry in slots {
+        let next = match entry {
+            Entry::Empty => EMPTY,
+            Entry::Child(b) => b as u64,
+            Entry::Leaf(value) => {
+                unsafe {
+                    ptr::copy_nonoverlapping(&value as *const V as *const u8,
+                                             bytes[mem::size_of::<u64>()..].as_mut_ptr(), mem::size_of::<V>());
+                }
+                len += 1;
+                LEAF
+            }
+        };//match entry
+        if next != LEAF {
+            for byte in bytes[mem::size_of::<u64>()..].iter_mut() {
+                *byte = 0;
+            }//for
+        }//if
+        bytes[..mem::size_of::<u64>()].copy_from_slice(&unsafe { mem::transmute::<u64, [u8; 8]>(next) });
+        hash = checksum(hash, &bytes);
+        out.write_all(&bytes)?;
+        nslots += 1;
+    }//for
+
+    let mut header = Header {
+        magic: MAGIC,
+        version: VERSION,
+        byte_order: BYTE_ORDER,
+        header_size: HEADER_SIZE as u32,
+        key_bits: key_bits as u32,
+        key_length: segments.iter().sum::<usize>() as u32,
+        levels: segments.len() as u32,
+        value_size: mem::size_of::<V>() as u32,
+        slot_size: slot_size as u32,
+        segments: [0; MAX_LEVELS],
+        len,
+        slots: nslots,
+        checksum: hash,
+    };
+    for (depth, &size) in segments.iter().enumerate() {
+        header.segments[depth] = size as u8;
+    }//for
+    let header = unsafe { mem::transmute::<Header, [u8; HEADER_SIZE]>(header) };
+
+    let mut file = out.into_inner().map_err(|e| e.into_error())?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&header)?;
+    file.sync_all()
+}//write
+
+/// Read-only ContiguousTrie mapped from a file written by ContiguousTrie::save
+/// Lookups walk the mapped slots from the root block down, following the child offsets.
+/// The file must not be modified while it is mapped.
+#[derive(Debug)]
+pub struct MappedContiguousTrie<K: PrimInt, V: Pod> {
+    map: *mut libc::c_void,
+    map_len: usize,
+    nslots: usize,
+    len: usize,
+    key_length: usize,
+    segments: Vec<usize>,
+    shifts: Vec<usize>,
+    _key: PhantomData<K>,
+    _value: PhantomData<V>,
+}//struct MappedContiguousTrie
+
+// the mapping is never written to, so it can be read from any thread
+unsafe impl<K: PrimInt, V: Pod> Send for MappedContiguousTrie<K, V> {}
+unsafe impl<K: PrimInt, V: Pod> Sync for MappedContiguousTrie<K, V> {}
+
+impl<K: PrimInt, V: Pod> MappedContiguousTrie<K, V> {
+    // map the file at path, checking that it holds a trie with keys of type K and values of type V
+    // checking the checksum reads the whole file once
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let map_len = file.metadata()?.len() as usize;
+        if map_len < HEADER_SIZE {
+            return Err(invalid("file is too short to hold a header"));
+        }//if
+        let map = unsafe {
+            libc::mmap(ptr::null_mut(), map_len as libc::size_t, libc::PROT_READ, libc::MAP_SHARED, file.as_raw_fd(), 0)
+        };
+        if map == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }//if
+
+        let mut trie = MappedContiguousTrie {
+            map,
+            map_len,
+            nslots: 0,
+            len: 0,
+            key_length: 0,
+            segments: Vec::new(),
+            shifts: Vec::new(),
+            _key: PhantomData,
+            _value: PhantomData,
+        };
+        trie.validate()?; // dropping trie unmaps the file on an error
+        Ok(trie)
+    }//open
+
+    // check the header and the checksum of the mapped file, and fill in the fields from the header
+    fn validate(&mut self) -> io::Result<()> {
+        // the mapping is page aligned, so the header can be read in place
+        let header = unsafe { &*(self.map as *const Header) };
+        if header.magic != MAGIC {
+            return Err(invalid("not a saved ContiguousTrie"));
+        }//if
+        if header.byte_order != BYTE_ORDER {
+            return Err(invalid("saved on a machine of another byte order"));
+        }//if
+        if header.version != VERSION || header.header_size as usize != HEADER_SIZE {
+            return Err(invalid("unsupported file version"));
+        }//if
+        if header.key_bits as usize != K::BITS {
+            return Err(invalid("saved with another key type"));
+        }//if
+        if header.value_size as usize != mem::size_of::<V>() || header.slot_size as usize != mem::size_of::<Slot<V>>() {
+            return Err(invalid("saved with another value type"));
+        }//if
+
+        let levels = header.levels as usize;
+        if levels == 0 || levels > MAX_LEVELS {
+            return Err(invalid("bad number of levels"));
+        }//if
+        let segments: Vec<usize> = header.segments[..levels].iter().map(|&size| size as usize).collect();
+        let key_length: usize = segments.iter().sum();
+        if segments.iter().any(|&size| size == 0 || size >= 64)
+            || key_length != header.key_length as usize || key_length > K::BITS {
+            return Err(invalid("bad segment sizes"));
+        }//if
+
+        let nslots = header.slots as usize;
+        let expected = (nslots as u64).checked_mul(mem::size_of::<Slot<V>>() as u64)
+            .and_then(|bytes| bytes.checked_add(HEADER_SIZE as u64));
+        if expected != Some(self.map_len as u64) || nslots < 1 << segments[0] || header.len > header.slots {
+            return Err(invalid("file length doesn't match the header"));
+        }//if
+        let bytes = unsafe {
+            slice::from_raw_parts((self.map as *const u8).offset(HEADER_SIZE as isize), self.map_len - HEADER_SIZE)
+        };
+        if checksum(CHECKSUM_SEED, bytes) != header.checksum {
+            return Err(invalid("checksum mismatch"));
+        }//if
+
+        let mut shifts = Vec::with_capacity(levels);
+        let mut consumed = 0;
+        for &size in segments.iter() {
+            consumed += size;
+            shifts.push(key_length - consumed);
+        }//for
+
+        self.nslots = nslots;
+        self.key_length = key_length;
+        self.segments = segments;
+        self.shifts = shifts;
+        // the checksum only catches damage, a file written wrong could still point get outside the slots
+        if self.check_links() != header.len {
+            return Err(invalid("bad block offsets"));
+        }//if
+        self.len = header.len as usize;
+        Ok(())
+    }//validate
+
+    // walk every block reachable from the root, checking that each one lies inside the slots and
+    // overlaps no other block, and that leaves only sit at the last level
+    // return the number of leaves found, or u64::MAX at the first bad offset
+    fn check_links(&self) -> u64 {
+        let slots = self.slots();
+        let mut taken = vec![false; slots.len()];
+        let mut leaves = 0;
+        let mut stack = vec![(0, 0)]; // (start, depth) of the blocks left to check
+        while let Some((start, depth)) = stack.pop() {
+            let end = start + (1 << self.segments[depth]);
+            if end > slots.len() || taken[start..end].iter().any(|&t| t) {
+                return u64::MAX;
+            }//if
+            for index in start..end {
+                taken[index] = true;
+                let next = slots[index].next;
+                if next == EMPTY {
+                    continue;
+                }//if
+                if depth + 1 == self.segments.len() {
+                    if next != LEAF {
+                        return u64::MAX;
+                    }//if
+                    leaves += 1;
+                } else if next == LEAF || next >= slots.len() as u64 {
+                    return u64::MAX;
+                } else {
+                    stack.push((next as usize, depth + 1));
+                }//if
+            }//for
+        }//while
+        leaves
+    }//check_links
+
+    #[inline(always)]
+    fn slots(&self) -> &[Slot<V>] {
+        unsafe {
+            slice::from_raw_parts((self.map as *const u8).offset(HEADER_SIZE as isize) as *const Slot<V>, self.nslots)
+        }
+    }//slots
+
+    #[inline(always)]
+    fn compute_index(&self, key: u64, depth: usize) -> usize {
+        ((key >> self.shifts[depth]) & ((1 << self.segments[depth]) - 1)) as usize
+    }//compute_index
+
+    // the number of entries
+    pub fn len(&self) -> usize {
+        self.len
+    }//len
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }//is_empty
+
+    // the number of bytes mapped, header included
+    pub fn mapped_bytes(&self) -> usize {
+        self.map_len
+    }//mapped_bytes
+
+    // return the value in the given key and wrap it with an Option, or an error for a key that doesn't fit
+    #[inline(always)]
+    pub fn get(&self, key: K) -> Result<Option<V>, TrieError> {
+        let key = key.to_u64();
+        if self.key_length < 64 && key >> self.key_length != 0 {
+            return Err(TrieError::OutOfRange);
+        }//if
+        let slots = self.slots();
+        let mut current_index = self.compute_index(key, 0);
+        for depth in 1..self.segments.len() {
+            let next = slots[current_index].next;
+            if next >= LEAF {
+                return Ok(None); // the key's block was never allocated
+            }//if
+            current_index = next as usize + self.compute_index(key, depth);
+        }//for
+        let slot = &slots[current_index];
+        Ok(if slot.next == LEAF { Some(slot.value) } else { None })
+    }//get
+
+    // return true if the key entry exists
+    #[inline(always)]
+    pub fn contain(&self, key: K) -> bool {
+        match self.get(key) {
+            Ok(found) => found.is_some(),
+            Err(_) => false,
+        }//match
+    }//contain
+}//impl MappedContiguousTrie
+
+impl<K: PrimInt, V: Pod> Drop for MappedContiguousTrie<K, V> {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.map, self.map_len as libc::size_t); }
+    }//drop
+}//impl Drop for MappedContiguousTrie
@@ -2,6 +2,8 @@
 
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::mem;
+use error::TrieError;
 
 pub trait TrieData: Clone + Copy + Eq + PartialEq {}
 
@@ -99,12 +101,25 @@ impl<T: TrieData> MutexContiguousTrie<T> {
         return id as usize;
     }
 
+    // reject keys that aren't key_length '0'/'1' digits, compute_index would read garbage
+    #[inline(always)]
+    fn check(&self, key: &[u8]) -> Result<(), TrieError> {
+        if key.len() != self.key_length {
+            return Err(TrieError::WrongLength);
+        }//if
+        if key.iter().any(|&b| b != b'0' && b != b'1') {
+            return Err(TrieError::InvalidDigit);
+        }//if
+        Ok(())
+    }//check
+
     // key should be 1-1 mapping to self memory array
+    // this: the memory, locked by the caller for the whole operation so that a concurrent insert
+    // or remove can't run between the walk and the access to the slot
     #[inline(always)]
-    fn key2index(&self, key: &[u8]) -> usize {
+    fn key2index(&self, this: &Vec<Option<SubTrie<T>>>, key: &[u8]) -> usize {
         let mut current_index = self.compute_index(key);
         let mut key_start = 0;
-        let mut this = self.memory.lock().unwrap(); //lock during access
         while (*this).len() > current_index && (*this)[current_index].is_some() {
 //            println!("comp_index {} ci {} {}", self.compute_index(&key[key_start..]), current_index, self.memory.len());
             match &(*this)[current_index] {
@@ -124,10 +139,32 @@ impl<T: TrieData> MutexContiguousTrie<T> {
     }//key2index
 
     //insert the value into the Trie
-    pub fn insert(&self, value: T, key: &[u8]) {
-        let current_index = self.key2index(key);
+    //return the value that was stored under the key before, if any
+    pub fn insert(&self, value: T, key: &[u8]) -> Result<Option<T>, TrieError> {
+        self.check(key)?;
         let mut this = self.memory.lock().unwrap(); //lock during access
+        let current_index = self.key2index(&this, key);
 //        println!("debug {} {}", current_index, self.memory.len());
+        if current_index >= (*this).len() {
+            let push_amount = current_index - (*this).len() + 1;
+            for _ in 0..push_amount {
+                (*this).push(None);
+            }//for
+        }//if
+        let leaf = Some(SubTrie {
+            data: Some(value),
+//            depth: get_depth(self.key_length, current_index),
+            depth: 0,
+            children_offset: None,
+        });
+        Ok(mem::replace(&mut (*this)[current_index], leaf).and_then(|old| old.data))
+    }//insert
+
+    //insert the value only if the key isn't in the Trie yet
+    pub fn try_insert(&self, value: T, key: &[u8]) -> Result<(), TrieError> {
+        self.check(key)?;
+        let mut this = self.memory.lock().unwrap(); //lock during access
+        let current_index = self.key2index(&this, key);
         if current_index >= (*this).len() {
             let push_amount = current_index - (*this).len() + 1;
             for _ in 0..push_amount {
@@ -135,21 +172,24 @@ impl<T: TrieData> MutexContiguousTrie<T> {
             }//for
         }//if
         if (*this)[current_index].is_some() {
-            assert!(false);
+            return Err(TrieError::Duplicate);
         }//if
         (*this)[current_index] = Some(SubTrie {
             data: Some(value),
-//            depth: get_depth(self.key_length, current_index),
             depth: 0,
             children_offset: None,
         });
-    }//insert
+        Ok(())
+    }//try_insert
 
     //return true if the key entry exists
     #[inline(always)]
     pub fn contain(&self, key: &[u8]) -> bool {
-        let current_index = self.key2index(key);
+        if self.check(key).is_err() {
+            return false; // a malformed key can't be in the trie
+        }//if
         let mut this = self.memory.lock().unwrap(); //lock during access
+        let current_index = self.key2index(&this, key);
         if (*this).len() <= current_index {
             return false; // can't contain the key entry if the key index is greater than the length of memory
         }//if
@@ -161,21 +201,47 @@ impl<T: TrieData> MutexContiguousTrie<T> {
         }//match
     }//contain
 
-    // return the value in the given key and wrap it with an Option
+    // return the value in the given key and wrap it with an Option, or an error for a malformed key
     #[inline(always)]
-    pub fn get(&self, key: &[u8]) -> Option<T> {
-        let current_index = self.key2index(key);
+    pub fn get(&self, key: &[u8]) -> Result<Option<T>, TrieError> {
+        self.check(key)?;
         let mut this = self.memory.lock().unwrap(); //lock during access
+        let current_index = self.key2index(&this, key);
         if (*this).len() <= current_index {
-            return None; // can't return anything if we're out of memory bounds
+            return Ok(None); // can't return anything if we're out of memory bounds
         }
         match &(*this)[current_index] {
             Some(a) => {
-                a.data // return the data at the given index
+                Ok(a.data) // return the data at the given index
             }
-            None => None, // if there's nothing at the given index, return None
+            None => Ok(None), // if there's nothing at the given index, return None
         }//match
     }//get
+
+    // remove the entry and return its value
+    pub fn remove(&self, key: &[u8]) -> Result<Option<T>, TrieError> {
+        self.check(key)?;
+        let mut this = self.memory.lock().unwrap(); //lock during access
+        let current_index = self.key2index(&this, key);
+        if (*this).len() <= current_index {
+            return Ok(None); // nothing was ever inserted that far
+        }//if
+        Ok(mem::replace(&mut (*this)[current_index], None).and_then(|old| old.data))
+    }//remove
+
+    // drop the trailing empty slots that insert pushed, and release the spare capacity
+    pub fn shrink_to_fit(&self) {
+        let mut this = self.memory.lock().unwrap(); //lock during access
+        while let Some(&None) = (*this).last() {
+            (*this).pop();
+        }//while
+        (*this).shrink_to_fit();
+    }//shrink_to_fit
+
+    // the number of slots in memory
+    pub fn allocated_slots(&self) -> usize {
+        self.memory.lock().unwrap().len()
+    }//allocated_slots
 }//impl MutexContiguousTrie
 
 // TODO should change this to key_length+2, which is {:0key_length+2b}
@@ -196,7 +262,7 @@ fn main() {
             for i in begin..end {
                 let str = binary_format!(i);
                 let arr = str.to_owned().into_bytes();
-				trie.insert(i, &arr[2..]);
+				trie.insert(i, &arr[2..]).unwrap();
             }
         });
     }
@@ -209,7 +275,7 @@ fn main() {
             for i in begin..end {
                 let str = binary_format!(i);
                 let arr = str.to_owned().into_bytes();
-                assert_eq!(thread_trie.get(&arr[2..]).unwrap(), i);
+                assert_eq!(thread_trie.get(&arr[2..]), Ok(Some(i)));
             }
         });
     }
@@ -1,8 +1,29 @@
 /// Concurrent Cache Conscious Hash Trie using Read-Write Lock
+///
+/// The interior nodes are all allocated by new and never written again, so walking them takes no
+/// lock. The leaves are a separate array behind the RwLock, in key order, grown on demand.
+///
+/// get_optimistic reads a leaf without taking the lock, seqlock style: every block of leaves has a
+/// sequence counter that writers make odd while they update the block, and a reader retries until
+/// it sees the same even counter before and after its read. Since that read races with the
+/// writers, a leaf is kept as atomic words (a tag, then the bytes of the value) that writers only
+/// store atomically. A racing read may put together a value out of two writes before it retries,
+/// so get_optimistic is only there for Pod values, which take any bit pattern; the other values
+/// are read under the lock.
+///
+/// A leaf array replaced by another one stays allocated while an optimistic reader may still be
+/// reading it: every reader counts itself in for the time of its read, in a counter on its own
+/// cache line, and a replaced array is freed once each counter was seen at zero since. The
+/// counters are checked again on every write, so the arrays are freed under steady reads too.
 
 use std::sync::{Arc, RwLock};
+use std::sync::atomic::{self, AtomicPtr, AtomicUsize, Ordering};
 use std::thread;
 use std::time::SystemTime;
+use std::mem;
+use std::ptr;
+use mapped_cchamt::Pod;
+use error::TrieError;
 
 pub trait TrieData: Clone + Copy + Eq + PartialEq {}
 
@@ -26,14 +47,59 @@ fn get_depth(key_group: usize, index: usize) -> usize {
     depth
 }
 
+// bytes in a word of a leaf
+const WORD: usize = mem::size_of::<usize>();
+// there are NREADERS reader counters, threads beyond that share one
+// NREADERS is at most 64, so that a bit set of the counters fits in a u64
+const NREADERS: usize = 64;
+// the bit set of all the reader counters
+const ALL_READERS: u64 = !0 >> (64 - NREADERS);
+
+// hands out a small sequential id per thread, used to pick its reader counter
+static NEXT_READER: AtomicUsize = AtomicUsize::new(0);
+
+thread_local!(static READER: usize = NEXT_READER.fetch_add(1, Ordering::Relaxed) % NREADERS);
+
+// a counter on its own cache line, so that readers on different threads don't share a line
+#[derive(Debug)]
+#[repr(align(64))]
+struct ReaderCount(AtomicUsize);
+
+// the words of a leaf array, leaf i takes the words [i * stride, (i + 1) * stride)
+type Words = Box<[AtomicUsize]>;
+
 /// Core Data structure
 #[derive(Debug)]
 pub struct RwContiguousTrie<T: TrieData> {
-    memory: RwLock<Vec<Option<SubTrie<T>>>>,
+    interior: Vec<Option<SubTrie<T>>>,
+    memory: RwLock<Leaves>,
+    current: AtomicPtr<Words>, // the leaf array optimistic readers read
+    versions: Box<[AtomicUsize]>, // sequence counter of every block of leaves, odd during an update
+    readers: Box<[ReaderCount]>, // optimistic readers in the middle of a read, by thread
+    stride: usize, // words per leaf
     key_length: usize,
     key_segment_size: usize,
 }
 
+// leaf i of the trie is at index interior.len() + i of the whole memory
+#[derive(Debug)]
+struct Leaves {
+    slots: Box<Words>, // never resized once published, another array replaces it
+    len: usize, // slots in use, up to the last leaf inserted
+    retired: Vec<(Box<Words>, u64)>, // replaced arrays, with the reader counters not seen at zero since
+}
+
+// count of zeroed words: zeroed memory comes straight from the kernel, so the words that are never
+// written don't take any memory, and AtomicUsize has the same layout as usize
+fn zeroed_words(count: usize) -> Box<[AtomicUsize]> {
+    let mut zeroed = vec![0usize; count];
+    unsafe {
+        let words = Vec::from_raw_parts(zeroed.as_mut_ptr() as *mut AtomicUsize, zeroed.len(), zeroed.capacity());
+        mem::forget(zeroed);
+        words.into_boxed_slice()
+    }
+}
+
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct SubTrie<T: TrieData> {
@@ -47,7 +113,8 @@ impl<T: TrieData> RwContiguousTrie<T> {
     pub fn new(key_length: usize, key_segment_size: usize) -> Self {
         assert_eq!(key_length % key_segment_size, 0);
 
-        let memory: RwLock<Vec<Option<SubTrie<T>>>>;
+        let mut interior: Vec<Option<SubTrie<T>>>;
+        let leaf_blocks;
         // init with all nodes that is not leaf
         // length = summation of KEY_LEN^1 to KEY_LEN^(KEY_LEN/KEY_GROUP-1)
         {
@@ -58,12 +125,12 @@ impl<T: TrieData> RwContiguousTrie<T> {
                 nodes_length += multitude;
                 multitude *= array_length;
             }
+            leaf_blocks = multitude / array_length;
 //            println!("nl {}", nodes_length);
-            memory = RwLock::new(Vec::with_capacity(nodes_length));
+            interior = Vec::with_capacity(nodes_length);
 
-            let mut this = memory.write().unwrap();
             for i in 0..nodes_length {
-                (*this).push(Some(SubTrie {
+                interior.push(Some(SubTrie {
                     data: None,
 //                    depth: get_depth(key_segment_size as usize, i),
                     depth: 0,
@@ -73,8 +140,15 @@ impl<T: TrieData> RwContiguousTrie<T> {
             }
         }
 
+        let mut slots = Box::new(zeroed_words(0));
+        let current = AtomicPtr::new(&mut *slots as *mut Words);
         RwContiguousTrie {
-            memory,
+            interior,
+            memory: RwLock::new(Leaves { slots, len: 0, retired: Vec::new() }),
+            current,
+            versions: zeroed_words(leaf_blocks),
+            readers: (0..NREADERS).map(|_| ReaderCount(AtomicUsize::new(0))).collect::<Vec<_>>().into_boxed_slice(),
+            stride: 1 + (mem::size_of::<T>() + WORD - 1) / WORD,
             key_length,
             key_segment_size,
         }
@@ -93,92 +167,268 @@ impl<T: TrieData> RwContiguousTrie<T> {
         return id as usize;
     }
 
+    // reject keys that aren't key_length '0'/'1' digits, compute_index would read garbage
+    #[inline(always)]
+    fn check(&self, key: &[u8]) -> Result<(), TrieError> {
+        if key.len() != self.key_length {
+            return Err(TrieError::WrongLength);
+        }
+        if key.iter().any(|&b| b != b'0' && b != b'1') {
+            return Err(TrieError::InvalidDigit);
+        }
+        Ok(())
+    }
+
     // key should be 1-1 mapping to self memory array
+    // return the index of the key's leaf in the leaf array
     #[inline(always)]
     fn key2index(&self, key: &[u8]) -> usize {
         let mut current_index = self.compute_index(key);
         let mut key_start = 0;
-        let this = self.memory.read().unwrap();
-        while (*this).len() > current_index && (*this)[current_index].is_some() {
+        while self.interior.len() > current_index {
 //            println!("comp_index {} ci {} {}", self.compute_index(&key[key_start..]), current_index, self.memory.len());
-            match &(*this)[current_index] {
-                Some(a) => {
-                    match a.children_offset {
-                        Some(b) => {
-                            key_start += self.key_segment_size;
-                            current_index = b + self.compute_index(&key[key_start..]);
-                        }
-                        None => break,
-                    }
+            match &self.interior[current_index] {
+                Some(SubTrie { children_offset: Some(b), .. }) => {
+                    key_start += self.key_segment_size;
+                    current_index = b + self.compute_index(&key[key_start..]);
                 }
-                None => break,
+                _ => break,
             }
         }
-        current_index
+        current_index - self.interior.len()
     }
 
-    pub fn insert(&self, value: T, key: &[u8]) {
-        let current_index = self.key2index(key);
-        let mut length = 0;
-        {
-            let this = self.memory.read().unwrap();
-            length = (*this).len();
-        }
-//        println!("debug {} {}", current_index, self.memory.len());
-        if current_index >= length {
-            let push_amount = current_index - length + 1;
-            {
-                let mut this = self.memory.write().unwrap();
-                for _ in 0..push_amount {
-                    (*this).push(None);
+    // the sequence counter of the block holding the leaf
+    #[inline(always)]
+    fn version(&self, leaf: usize) -> &AtomicUsize {
+        &self.versions[leaf >> self.key_segment_size]
+    }
+
+    // the number of leaves of a leaf array
+    #[inline(always)]
+    fn leaves(&self, words: &Words) -> usize {
+        words.len() / self.stride
+    }
+
+    // read the leaf out of words, which has to be in range, the caller holds the lock
+    // no writer runs meanwhile, so the value is read in one go
+    #[inline(always)]
+    fn load(&self, words: &Words, leaf: usize) -> Option<T> {
+        let base = leaf * self.stride;
+        if words[base].load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+        Some(unsafe { ptr::read_unaligned(&words[base + 1] as *const AtomicUsize as *const T) })
+    }
+
+    // write value into the leaf of words, which has to be in range
+    #[inline(always)]
+    fn write(&self, words: &Words, leaf: usize, value: Option<T>) {
+        let base = leaf * self.stride;
+        match value {
+            None => words[base].store(0, Ordering::Relaxed),
+            Some(value) => {
+                let bytes = &value as *const T as *const u8;
+                for i in 0..self.stride - 1 {
+                    let mut word = 0usize;
+                    let n = if mem::size_of::<T>() - i * WORD < WORD { mem::size_of::<T>() - i * WORD } else { WORD };
+                    unsafe { ptr::copy_nonoverlapping(bytes.offset((i * WORD) as isize), &mut word as *mut usize as *mut u8, n); }
+                    words[base + 1 + i].store(word, Ordering::Relaxed);
                 }
+                words[base].store(1, Ordering::Relaxed);
             }
         }
+    }
 
-        {
-            let this = self.memory.read().unwrap();
-            if (*this)[current_index].is_some() {
-                assert!(false);
+    // replace the leaf array by one of length leaves holding the first this.len leaves, the caller
+    // holds the write lock; the old array is kept for the optimistic readers still reading it
+    fn replace(&self, this: &mut Leaves, leaves: usize) {
+        let mut slots = Box::new(zeroed_words(leaves * self.stride));
+        for i in 0..this.len * self.stride {
+            slots[i].store(this.slots[i].load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        // SeqCst pairs with the readers counting themselves in, see collect
+        self.current.store(&mut *slots as *mut Words, Ordering::SeqCst);
+        let old = mem::replace(&mut this.slots, slots);
+        this.retired.push((old, ALL_READERS));
+        self.collect(this);
+    }
+
+    // free the replaced leaf arrays no optimistic reader can still be reading, the caller holds
+    // the write lock
+    // a reader counts itself in before it loads current, so once a counter is seen at zero after
+    // current moved on, every reader it counted either finished or is reading the new array; the
+    // counters don't have to be at zero all at once
+    fn collect(&self, this: &mut Leaves) {
+        if this.retired.is_empty() {
+            return;
+        }
+        let mut idle = 0u64;
+        for (i, count) in self.readers.iter().enumerate() {
+            if count.0.load(Ordering::SeqCst) == 0 {
+                idle |= 1 << i;
             }
         }
+        for &mut (_, ref mut busy) in this.retired.iter_mut() {
+            *busy &= !idle;
+        }
+        this.retired.retain(|&(_, busy)| busy != 0);
+    }
+
+    // grow the leaf array so that leaf is in range, the caller holds the write lock
+    // the array is replaced by one twice as large
+    fn reserve(&self, this: &mut Leaves, leaf: usize) {
+        let leaves = self.leaves(&this.slots);
+        if leaf >= leaves {
+            self.replace(this, if leaf + 1 > 2 * leaves { leaf + 1 } else { 2 * leaves });
+        }
+        if leaf >= this.len {
+            this.len = leaf + 1;
+        }
+    }
+
+    // store value in the leaf, which has to be in range, and return what was there
+    // the block's counter is odd while the leaf is written, so optimistic readers retry
+    // every write also tries again to free the leaf arrays replaced so far
+    fn store(&self, this: &mut Leaves, leaf: usize, value: Option<T>) -> Option<T> {
+        let old = self.load(&this.slots, leaf);
+        let version = self.version(leaf);
+        version.store(version.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+        self.write(&this.slots, leaf, value);
+        version.store(version.load(Ordering::Relaxed) + 1, Ordering::Release);
+        self.collect(this);
+        old
+    }
+
+    // return the value that was stored under the key before, if any
+    // growing the leaves and storing the value happen under a single write lock, so racing
+    // inserts of the same key are ordered and each one returns the value of the one before
+    pub fn insert(&self, value: T, key: &[u8]) -> Result<Option<T>, TrieError> {
+        self.check(key)?;
+        let leaf = self.key2index(key); // the interior never changes, no lock needed
 
         let mut this = self.memory.write().unwrap();
-        (*this)[current_index] = Some(SubTrie {
-            data: Some(value),
-//            depth: get_depth(self.key_length, current_index),
-            depth: 0,
-            children_offset: None,
-        });
+        self.reserve(&mut this, leaf);
+        Ok(self.store(&mut this, leaf, Some(value)))
+    }
+
+    // insert the value only if the key isn't in the trie yet
+    // the check and the store are in the same critical section, so only one racing insert wins
+    pub fn try_insert(&self, value: T, key: &[u8]) -> Result<(), TrieError> {
+        self.check(key)?;
+        let leaf = self.key2index(key);
+
+        let mut this = self.memory.write().unwrap();
+        self.reserve(&mut this, leaf);
+        if self.load(&this.slots, leaf).is_some() {
+            return Err(TrieError::Duplicate);
+        }
+        self.store(&mut this, leaf, Some(value));
+        Ok(())
     }
 
     #[inline(always)]
     pub fn contain(&self, key: &[u8]) -> bool {
-        let current_index = self.key2index(key);
-        let mut this = self.memory.read().unwrap();
-        if (*this).len() <= current_index {
-            return false;
-        }
-        match &(*this)[current_index] {
-            Some(_) => {
-                true
-            }
-            None => false,
+        match self.get(key) {
+            Ok(found) => found.is_some(),
+            Err(_) => false, // a malformed key can't be in the trie
+        }
+    }
+
+    #[inline(always)]
+    pub fn get(&self, key: &[u8]) -> Result<Option<T>, TrieError> {
+        self.check(key)?;
+        let leaf = self.key2index(key);
+        let this = self.memory.read().unwrap();
+        if self.leaves(&this.slots) <= leaf {
+            return Ok(None);
+        }
+        Ok(self.load(&this.slots, leaf))
+    }
+
+    // remove the entry and return its value
+    pub fn remove(&self, key: &[u8]) -> Result<Option<T>, TrieError> {
+        self.check(key)?;
+        let leaf = self.key2index(key);
+        let mut this = self.memory.write().unwrap();
+        if self.leaves(&this.slots) <= leaf {
+            return Ok(None);
+        }
+        Ok(self.store(&mut this, leaf, None))
+    }
+
+    // drop the trailing empty leaves that insert reserved, and release the spare capacity
+    // together with the leaf arrays replaced so far that no optimistic reader is reading any more
+    pub fn shrink_to_fit(&self) {
+        let mut this = self.memory.write().unwrap();
+        while this.len > 0 && self.load(&this.slots, this.len - 1).is_none() {
+            this.len -= 1;
+        }
+        if this.len < self.leaves(&this.slots) {
+            let len = this.len;
+            self.replace(&mut this, len);
+        } else {
+            self.collect(&mut this);
         }
     }
 
+    // the number of slots in memory, interior nodes included
+    pub fn allocated_slots(&self) -> usize {
+        self.interior.len() + self.memory.read().unwrap().len
+    }
+
+    // the number of replaced leaf arrays not freed yet, as optimistic readers may still read them
+    pub fn retired_arrays(&self) -> usize {
+        self.memory.read().unwrap().retired.len()
+    }
+}
+
+// the lock-free reads, for the values that take any bit pattern
+impl<T: Pod> RwContiguousTrie<T> {
+    // read the leaf out of words, which has to be in range, word by word
+    // a read racing with a store may see a mix of both values, the caller has to check the counter
     #[inline(always)]
-    pub fn get(&self, key: &[u8]) -> Option<T> {
-        let current_index = self.key2index(key);
-        let mut this = self.memory.read().unwrap();
-        if (*this).len() <= current_index {
+    fn load_racy(&self, words: &Words, leaf: usize) -> Option<T> {
+        let base = leaf * self.stride;
+        if words[base].load(Ordering::Relaxed) == 0 {
             return None;
         }
-        match &(*this)[current_index] {
-            Some(a) => {
-                a.data
-            }
-            None => None,
+        let mut value: T = unsafe { mem::zeroed() }; // Pod takes any bit pattern
+        let bytes = &mut value as *mut T as *mut u8;
+        for i in 0..self.stride - 1 {
+            let word = words[base + 1 + i].load(Ordering::Relaxed);
+            let n = if mem::size_of::<T>() - i * WORD < WORD { mem::size_of::<T>() - i * WORD } else { WORD };
+            unsafe { ptr::copy_nonoverlapping(&word as *const usize as *const u8, bytes.offset((i * WORD) as isize), n); }
         }
+        Some(value)
+    }
+
+    // same as get, but without taking the lock: the read is retried until no writer updated the
+    // key's block meanwhile, so readers only write to the counter of their own thread
+    #[inline(always)]
+    pub fn get_optimistic(&self, key: &[u8]) -> Result<Option<T>, TrieError> {
+        self.check(key)?;
+        let leaf = self.key2index(key);
+        let version = self.version(leaf);
+        let reader = &self.readers[READER.with(|&reader| reader)].0;
+        reader.fetch_add(1, Ordering::SeqCst); // the leaf arrays we load stay allocated until we leave
+        let value = loop {
+            let before = version.load(Ordering::Acquire);
+            if before & 1 == 1 {
+                atomic::spin_loop_hint(); // a writer is in the middle of an update
+                continue;
+            }
+            // loaded after the counter, so a reader that sees an update also sees the array it went to
+            let slots = unsafe { &*self.current.load(Ordering::SeqCst) };
+            let value = if leaf < self.leaves(slots) { self.load_racy(slots, leaf) } else { None };
+            atomic::fence(Ordering::Acquire);
+            if version.load(Ordering::Relaxed) == before {
+                break value;
+            }
+        };
+        reader.fetch_sub(1, Ordering::Release);
+        Ok(value)
     }
 }
 
@@ -210,7 +460,7 @@ fn main() {
             for i in begin..end {
                 let str = binary_format!(i);
                 let arr = str.to_owned().into_bytes();
-                thread_trie.insert(i, &arr[2..]);
+                thread_trie.insert(i, &arr[2..]).unwrap();
             }
         }));
     }
@@ -237,7 +487,7 @@ fn main() {
             for i in begin..end {
                 let str = binary_format!(i);
                 let arr = str.to_owned().into_bytes();
-                assert_eq!(thread_trie.get(&arr[2..]).unwrap(), i);
+                assert_eq!(thread_trie.get(&arr[2..]), Ok(Some(i)));
             }
         }));
     }
@@ -0,0 +1,293 @@
+/// Concurrent Cache Conscious Hash Trie using striped Mutex locks
+/// MutexContiguousTrie and RwContiguousTrie put one lock around the whole memory, so every
+/// operation waits for every other one. Here each leaf slot is guarded by one of a fixed array of
+/// locks, picked by hashing the index of the slot, and operations on leaves under different locks
+/// run in parallel.
+///
+/// The memory has the same layout as MutexContiguousTrie's: new allocates every interior node, at
+/// children_offset = (i + 1) * array_length, and leaf i of the trie is at interior.len() + i, in
+/// key order. The interior is never written again, so walking it takes no lock. The leaves are cut
+/// into chunks of 2^chunk_bits slots that are allocated on first use, instead of one array that
+/// grows, so that a leaf never moves while another thread holds its lock.
+/// Keys are the same '0'/'1' strings as the single-lock tries take.
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::cell::UnsafeCell;
+use std::thread;
+use std::mem;
+use std::ptr;
+use std::slice;
+use error::TrieError;
+
+pub trait TrieData: Clone + Copy + Eq + PartialEq {}
+
+impl<T> TrieData for T where T: Clone + Copy + Eq + PartialEq {}
+
+// there are 2^STRIPE_BITS locks
+const STRIPE_BITS: usize = 6;
+// a chunk of leaves has at least 2^MIN_CHUNK_BITS slots, so that the table of chunks stays small
+const MIN_CHUNK_BITS: usize = 16;
+
+// a lock on its own cache line, so that threads holding neighbouring locks don't share a line
+#[derive(Debug)]
+#[repr(align(64))]
+struct Stripe(Mutex<()>);
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SubTrie<T: TrieData> {
+    pub data: Option<T>,
+    depth: usize,
+    children_offset: Option<usize>,    // the start position in allocator that place the array in hash trie
+}//struct SubTrie
+
+/// Core Data structure
+#[derive(Debug)]
+pub struct StripedContiguousTrie<T: TrieData> {
+    interior: Vec<Option<SubTrie<T>>>,
+    chunks: Box<[AtomicPtr<UnsafeCell<Option<T>>>]>, // first slot of every chunk of leaves, null until used
+    stripes: Box<[Stripe]>,
+    chunk_bits: usize,
+    key_length: usize,
+    key_segment_size: usize,
+}//struct StripedContiguousTrie
+
+// the leaves are only read and written under their stripe, the chunks are published with a CAS
+unsafe impl<T: TrieData + Send> Sync for StripedContiguousTrie<T> {}
+
+// Contiguous store all the nodes contiguous with the sequential order of key
+impl<T: TrieData> StripedContiguousTrie<T> {
+    //constructor
+    // key_length: length of the key
+    // key_segment_size: length of a key segment (a key_group)
+    pub fn new(key_length: usize, key_segment_size: usize) -> Self {
+        // key_length needs to be multiple of key_segment_size
+        assert_eq!(key_length % key_segment_size, 0);
+
+        // init with all nodes that is not leaf, as MutexContiguousTrie does
+        // length = summation of KEY_LEN^1 to KEY_LEN^(KEY_LEN/KEY_GROUP-1)
+        let mut nodes_length = 0;
+        let array_length = usize::pow(2, key_segment_size as u32);
+        let mut multitude = array_length;
+        for _ in 0..(key_length / key_segment_size - 1) { //for the number of segments
+            nodes_length += multitude;
+            multitude *= array_length;
+        }//for
+        let mut interior = Vec::with_capacity(nodes_length);
+        for i in 0..nodes_length {
+            interior.push(Some(SubTrie { //add a SubTrie in each index of the memory vector
+                data: None,
+                depth: 0,
+                children_offset: Some((i + 1) * array_length as usize),
+            }));
+        }//for
+
+        // multitude is now the number of leaves; a chunk holds whole blocks of leaves
+        let mut chunk_bits = if key_segment_size > MIN_CHUNK_BITS { key_segment_size } else { MIN_CHUNK_BITS };
+        if chunk_bits > key_length {
+            chunk_bits = key_length;
+        }//if
+        let chunks: Vec<_> = (0..multitude >> chunk_bits).map(|_| AtomicPtr::new(ptr::null_mut())).collect();
+        let stripes: Vec<Stripe> = (0..1 << STRIPE_BITS).map(|_| Stripe(Mutex::new(()))).collect();
+
+        StripedContiguousTrie {
+            interior,
+            chunks: chunks.into_boxed_slice(),
+            stripes: stripes.into_boxed_slice(),
+            chunk_bits,
+            key_length,
+            key_segment_size,
+        }
+    }//constructor
+
+    // return the index in the first <= 4 bits
+    // for instances: 0000 0000 -> 0
+    #[inline(always)]
+    fn compute_index(&self, key: &[u8]) -> usize {
+        let mut id = 0;
+        let length = if key.len() > self.key_segment_size { self.key_segment_size } else { key.len() };
+        for i in 0..length {
+            let temp = key[i] as usize - '0' as usize;
+            id += temp << (length - i - 1);
+        }//for
+        id
+    }//compute_index
+
+    // reject keys that aren't key_length '0'/'1' digits, compute_index would read garbage
+    #[inline(always)]
+    fn check(&self, key: &[u8]) -> Result<(), TrieError> {
+        if key.len() != self.key_length {
+            return Err(TrieError::WrongLength);
+        }//if
+        if key.iter().any(|&b| b != b'0' && b != b'1') {
+            return Err(TrieError::InvalidDigit);
+        }//if
+        Ok(())
+    }//check
+
+    // key should be 1-1 mapping to self memory array
+    // return the index of the key's leaf among the leaves
+    #[inline(always)]
+    fn key2index(&self, key: &[u8]) -> usize {
+        let mut current_index = self.compute_index(key);
+        let mut key_start = 0;
+        while self.interior.len() > current_index {
+            match &self.interior[current_index] {
+                Some(SubTrie { children_offset: Some(b), .. }) => {
+                    key_start += self.key_segment_size;
+                    current_index = b + self.compute_index(&key[key_start..]);
+                }//Some(b)
+                _ => break,
+            }//match
+        }//while
+        current_index - self.interior.len()
+    }//key2index
+
+    // the lock guarding the leaf
+    // a Fibonacci hash, so that the slots of a block spread over all the stripes
+    #[inline(always)]
+    fn stripe(&self, leaf: usize) -> &Mutex<()> {
+        let hash = (leaf as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (64 - STRIPE_BITS);
+        &self.stripes[hash as usize].0
+    }//stripe
+
+    // the slot of the leaf, or None if its chunk was never used
+    #[inline(always)]
+    fn slot(&self, leaf: usize) -> Option<&UnsafeCell<Option<T>>> {
+        let chunk = self.chunks[leaf >> self.chunk_bits].load(Ordering::Acquire);
+        if chunk.is_null() {
+            return None;
+        }//if
+        Some(unsafe { &*chunk.offset((leaf & ((1 << self.chunk_bits) - 1)) as isize) })
+    }//slot
+
+    // same as slot, allocating the chunk if needed
+    // racing threads may both allocate it, the one that loses the CAS frees its own
+    fn slot_mut(&self, leaf: usize) -> &UnsafeCell<Option<T>> {
+        if let Some(slot) = self.slot(leaf) {
+            return slot;
+        }//if
+        let chunk: Vec<UnsafeCell<Option<T>>> = (0..1 << self.chunk_bits).map(|_| UnsafeCell::new(None)).collect();
+        let chunk = Box::into_raw(chunk.into_boxed_slice()) as *mut UnsafeCell<Option<T>>;
+        match self.chunks[leaf >> self.chunk_bits].compare_exchange(ptr::null_mut(), chunk, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => {}
+            Err(_) => unsafe { self.free_chunk(chunk) }, // another thread published one first
+        }//match
+        self.slot(leaf).unwrap()
+    }//slot_mut
+
+    // chunk: the first slot of a chunk allocated by slot_mut
+    unsafe fn free_chunk(&self, chunk: *mut UnsafeCell<Option<T>>) {
+        drop(Box::from_raw(slice::from_raw_parts_mut(chunk, 1 << self.chunk_bits) as *mut [UnsafeCell<Option<T>>]));
+    }//free_chunk
+
+    // run f on the value of the leaf, holding its stripe
+    #[inline(always)]
+    fn with_leaf<R, F: FnOnce(&mut Option<T>) -> R>(&self, leaf: usize, slot: &UnsafeCell<Option<T>>, f: F) -> R {
+        let _guard = self.stripe(leaf).lock().unwrap(); //lock during access
+        f(unsafe { &mut *slot.get() })
+    }//with_leaf
+
+    //insert the value into the Trie
+    //return the value that was stored under the key before, if any
+    pub fn insert(&self, value: T, key: &[u8]) -> Result<Option<T>, TrieError> {
+        self.check(key)?;
+        let leaf = self.key2index(key); // the interior never changes, no lock needed
+        Ok(self.with_leaf(leaf, self.slot_mut(leaf), |data| mem::replace(data, Some(value))))
+    }//insert
+
+    //insert the value only if the key isn't in the Trie yet
+    pub fn try_insert(&self, value: T, key: &[u8]) -> Result<(), TrieError> {
+        self.check(key)?;
+        let leaf = self.key2index(key);
+        self.with_leaf(leaf, self.slot_mut(leaf), |data| {
+            if data.is_some() {
+                return Err(TrieError::Duplicate);
+            }//if
+            *data = Some(value);
+            Ok(())
+        })
+    }//try_insert
+
+    //return true if the key entry exists
+    #[inline(always)]
+    pub fn contain(&self, key: &[u8]) -> bool {
+        match self.get(key) {
+            Ok(found) => found.is_some(),
+            Err(_) => false, // a malformed key can't be in the trie
+        }//match
+    }//contain
+
+    // return the value in the given key and wrap it with an Option, or an error for a malformed key
+    #[inline(always)]
+    pub fn get(&self, key: &[u8]) -> Result<Option<T>, TrieError> {
+        self.check(key)?;
+        let leaf = self.key2index(key);
+        match self.slot(leaf) {
+            Some(slot) => Ok(self.with_leaf(leaf, slot, |data| *data)),
+            None => Ok(None), // nothing was ever inserted in the key's chunk
+        }//match
+    }//get
+
+    // remove the entry and return its value
+    // the chunk of the leaf stays allocated
+    pub fn remove(&self, key: &[u8]) -> Result<Option<T>, TrieError> {
+        self.check(key)?;
+        let leaf = self.key2index(key);
+        match self.slot(leaf) {
+            Some(slot) => Ok(self.with_leaf(leaf, slot, |data| data.take())),
+            None => Ok(None), // nothing was ever inserted in the key's chunk
+        }//match
+    }//remove
+
+    // the number of slots in memory, interior nodes included
+    pub fn allocated_slots(&self) -> usize {
+        let chunks = self.chunks.iter().filter(|chunk| !chunk.load(Ordering::Relaxed).is_null()).count();
+        self.interior.len() + (chunks << self.chunk_bits)
+    }//allocated_slots
+}//impl StripedContiguousTrie
+
+impl<T: TrieData> Drop for StripedContiguousTrie<T> {
+    fn drop(&mut self) {
+        for chunk in self.chunks.iter() {
+            let chunk = chunk.load(Ordering::Relaxed);
+            if !chunk.is_null() {
+                unsafe { self.free_chunk(chunk); }
+            }//if
+        }//for
+    }//drop
+}//impl Drop for StripedContiguousTrie
+
+// TODO should change this to key_length+2, which is {:0key_length+2b}
+#[macro_use]
+macro_rules! binary_format {
+    ($x:expr) => {
+        format!("{:#034b}", $x)
+    };
+}//binary_format
+
+fn main() {
+    let trie = Arc::new(StripedContiguousTrie::<usize>::new(32, 8));
+    let mut handles = vec![];
+    for t_id in 0..4 {
+        let trie = trie.clone();
+        let begin = t_id * 25000;
+        let end = (t_id + 1) * 25000;
+        handles.push(thread::spawn(move || {
+            for i in begin..end {
+                let str = binary_format!(i);
+                let arr = str.to_owned().into_bytes();
+                trie.insert(i, &arr[2..]).unwrap();
+            }
+        }));
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    for i in 0..100000 {
+        let str = binary_format!(i);
+        let arr = str.to_owned().into_bytes();
+        assert_eq!(trie.get(&arr[2..]), Ok(Some(i)));
+    }
+}
@@ -0,0 +1,211 @@
+extern crate cchamt;
+
+use std::sync::Arc;
+use std::thread;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use cchamt::{Allocator, DebugAllocator, NodeAllocator};
+
+#[test]
+fn test_alloc() {
+    let mem = Allocator::<u64>::new(1000);
+    let a = mem.alloc(1) as *mut u64;
+    let b = mem.alloc(2) as *mut u64;
+
+    assert_ne!(a, b);
+    assert_eq!(unsafe { *a }, 1);
+    assert_eq!(unsafe { *b }, 2);
+}
+
+#[test]
+fn test_thread_buffers_are_contiguous() {
+    let mem = Arc::new(Allocator::<usize>::with_chunk_size(100000, 64));
+    let mut thread_handle: Vec<thread::JoinHandle<_>> = vec![];
+
+    for t_id in 0..4 {
+        let thread_mem = mem.clone();
+        thread_handle.push(thread::spawn(move || {
+            let mut slots = vec![];
+            for _ in 0..1000 {
+                slots.push(thread_mem.alloc(t_id) as *mut usize as usize);
+            }
+            slots
+        }));
+    }
+
+    let mut all = vec![];
+    for thread in thread_handle {
+        let slots = thread.join().unwrap();
+        // within a chunk of 64 slots, a thread's nodes are next to each other
+        for pair in slots.chunks(64) {
+            for i in 1..pair.len() {
+                assert_eq!(pair[i] - pair[i - 1], std::mem::size_of::<usize>());
+            }
+        }
+        all.extend(slots);
+    }
+
+    all.sort();
+    all.dedup();
+    assert_eq!(all.len(), 4000);
+}
+
+#[test]
+fn test_dealloc_reuses_slot() {
+    let mem = Allocator::<u64>::new(1000);
+    let a = mem.alloc(1);
+    let pa = a as *mut u64;
+    mem.dealloc(a);
+
+    let b = mem.alloc(2) as *mut u64;
+    assert_eq!(pa, b);
+    assert_eq!(unsafe { *b }, 2);
+}
+
+#[test]
+fn test_retired_slots_are_reused_after_reclaim() {
+    let mut mem = Allocator::<u64>::with_chunk_size(3, 1);
+    let a = mem.alloc(1) as *mut u64;
+    let b = mem.alloc(2) as *mut u64;
+    mem.retire(unsafe { &mut *a });
+    mem.retire(unsafe { &mut *b });
+
+    // retired values stay readable until reclaim
+    assert_eq!(unsafe { *a }, 1);
+    let c = mem.alloc(3) as *mut u64;
+    assert!(c != a && c != b);
+
+    mem.reclaim();
+    let mut reused = vec![mem.alloc(4) as *mut u64, mem.alloc(5) as *mut u64];
+    reused.sort();
+    let mut expected = vec![a, b];
+    expected.sort();
+    assert_eq!(reused, expected);
+}
+
+#[test]
+fn test_concurrent_retire() {
+    // every slot retired while other threads retire theirs is recycled by the next reclaim
+    let mut mem = Arc::new(Allocator::<usize>::with_chunk_size(100000, 16));
+    let mut thread_handle: Vec<thread::JoinHandle<_>> = vec![];
+
+    for t_id in 0..8 {
+        let thread_mem = mem.clone();
+        thread_handle.push(thread::spawn(move || {
+            for _ in 0..1000 {
+                let slot = thread_mem.alloc(t_id);
+                thread_mem.retire(slot);
+            }
+        }));
+    }
+    for thread in thread_handle {
+        thread.join().unwrap();
+    }
+
+    let mem = Arc::get_mut(&mut mem).unwrap();
+    assert_eq!(mem.usage().retired, 8000);
+    mem.reclaim();
+    assert_eq!(mem.usage().retired, 0);
+    assert_eq!(mem.usage().in_use, 0);
+}
+
+// counts how many times a value was dropped
+struct Counted(Arc<AtomicUsize>);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_drop_runs_destructors_once() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    {
+        let mem = Allocator::<Counted>::with_chunk_size(1000, 16);
+        let mut slots = vec![];
+        for _ in 0..10 {
+            slots.push(mem.alloc(Counted(drops.clone())) as *mut Counted);
+        }
+        mem.dealloc(unsafe { &mut *slots[0] });
+        mem.dealloc(unsafe { &mut *slots[1] });
+        mem.retire(unsafe { &mut *slots[2] });
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+        // the rest of the thread's chunk was never handed out
+    }
+    assert_eq!(drops.load(Ordering::Relaxed), 10);
+}
+
+#[test]
+fn test_region_is_contiguous() {
+    let mem = Allocator::<u64>::with_chunk_size(10, 1);
+    let first = mem.alloc(0) as *mut u64;
+    {
+        let mut region = mem.region(4).unwrap();
+        let a = region.alloc(1) as *mut u64;
+        let b = region.alloc(2) as *mut u64;
+        assert_eq!(a as usize - first as usize, std::mem::size_of::<u64>());
+        assert_eq!(b as usize - a as usize, std::mem::size_of::<u64>());
+        assert_eq!(region.remaining(), 2);
+    }
+    assert!(mem.region(6).is_none());
+
+    // the two slots the region didn't use are reused first
+    let c = mem.alloc(3) as *mut u64;
+    let d = mem.alloc(4) as *mut u64;
+    assert!(c as usize > first as usize && (c as usize - first as usize) / 8 <= 4);
+    assert!(d as usize > first as usize && (d as usize - first as usize) / 8 <= 4);
+}
+
+#[repr(align(64))]
+struct Line([u64; 8]);
+
+#[test]
+fn test_slots_are_aligned() {
+    let mem = Allocator::<Line>::new(100);
+    for i in 0..10 {
+        let line = mem.alloc(Line([i; 8])) as *mut Line as usize;
+        assert_eq!(line % 64, 0);
+    }
+}
+
+#[test]
+#[should_panic(expected = "use after free")]
+fn test_debug_allocator_catches_use_after_free() {
+    let mem = DebugAllocator::<[u64; 4]>::new(100);
+    let a = mem.alloc([1; 4]) as *mut [u64; 4];
+    mem.validate(unsafe { &*a });
+    mem.dealloc(unsafe { &mut *a });
+    mem.validate(unsafe { &*a });
+}
+
+#[test]
+fn test_usage() {
+    let mem = Allocator::<u64>::with_chunk_size(1000, 16);
+    let a = mem.alloc(1);
+    mem.alloc(2);
+    mem.alloc(3);
+
+    let usage = mem.usage();
+    assert_eq!(usage.in_use, 3);
+    assert_eq!(usage.high_water, 16); // the thread reserved a whole chunk
+    assert_eq!(usage.reserved_bytes, 8000);
+    assert_eq!(usage.touched_bytes, 4096);
+
+    mem.dealloc(a);
+    assert_eq!(mem.usage().in_use, 2);
+    let mut values = vec![];
+    unsafe { mem.for_each_live(|v| values.push(*v)); }
+    assert_eq!(values, vec![2, 3]);
+}
+
+#[test]
+fn test_reserve_is_backed_lazily() {
+    // about 19GB of address space, only the pages that are written get memory
+    let mem = Allocator::<[u64; 24]>::reserve(100000000);
+    let a = mem.alloc([7; 24]);
+    assert_eq!(a[23], 7);
+
+    let usage = mem.usage();
+    assert_eq!(usage.reserved_bytes, 100000000 * 192);
+    assert!(usage.touched_bytes <= 256 * 192); // the thread's first chunk
+}
@@ -0,0 +1,96 @@
+extern crate cchamt;
+
+use std::sync::Arc;
+use std::thread;
+use cchamt::{AtomicContiguousTrie, TrieError};
+
+#[test]
+fn test_2_power_16_insert() {
+    let trie = AtomicContiguousTrie::<u32, u32>::new(32, 8, 1 << 18);
+    for i in 0..65536u32 {
+        trie.insert(i, i).unwrap();
+    }
+    for i in 0..65536u32 {
+        assert_eq!(trie.get(i), Ok(Some(i)));
+    }
+    assert_eq!(trie.len(), 65536);
+    assert_eq!(trie.get(65536), Ok(None));
+}
+
+#[test]
+fn test_insert_returns_previous_value() {
+    let trie = AtomicContiguousTrie::<u32, i16>::new(32, 8, 1 << 12);
+
+    assert_eq!(trie.insert(-1, 42), Ok(None));
+    assert_eq!(trie.insert(-2, 42), Ok(Some(-1)));
+    assert_eq!(trie.try_insert(3, 42), Err(TrieError::Duplicate));
+    assert_eq!(trie.get(42), Ok(Some(-2)));
+    assert_eq!(trie.remove(42), Ok(Some(-2)));
+    assert_eq!(trie.remove(42), Ok(None));
+    assert!(!trie.contain(42));
+    assert_eq!(trie.try_insert(3, 42), Ok(()));
+    assert_eq!(trie.len(), 1);
+}
+
+#[test]
+fn test_out_of_range_and_full() {
+    let trie = AtomicContiguousTrie::<u32, u8>::with_segments(&[4, 4], 32);
+    assert_eq!(trie.insert(1, 256), Err(TrieError::OutOfRange));
+    assert_eq!(trie.get(256), Err(TrieError::OutOfRange));
+
+    // the root block and one leaf block fit, a second leaf block doesn't
+    assert_eq!(trie.insert(1, 0x10), Ok(None));
+    assert_eq!(trie.insert(2, 0x1f), Ok(None));
+    assert_eq!(trie.insert(3, 0x20), Err(TrieError::Full));
+    assert_eq!(trie.allocated_slots(), 32);
+    assert_eq!(trie.get(0x1f), Ok(Some(2)));
+}
+
+#[test]
+fn test_concurrent_insert_and_get() {
+    let trie = Arc::new(AtomicContiguousTrie::<u32, u32>::new(24, 8, 1 << 20));
+    let writers: Vec<_> = (0..4u32).map(|t| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            // the threads interleave their keys, so they race to link the same blocks
+            for i in 0..50000u32 {
+                let key = i * 4 + t;
+                trie.insert(key, key).unwrap();
+            }
+        })
+    }).collect();
+    let readers: Vec<_> = (0..2).map(|_| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            for key in 0..200000u32 {
+                match trie.get(key) {
+                    Ok(Some(value)) => assert_eq!(value, key),
+                    Ok(None) => {}
+                    Err(e) => panic!("{:?}", e),
+                }
+            }
+        })
+    }).collect();
+    for handle in writers.into_iter().chain(readers) {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(trie.len(), 200000);
+    for key in 0..200000u32 {
+        assert_eq!(trie.get(key), Ok(Some(key)));
+    }
+}
+
+#[test]
+fn test_concurrent_try_insert_has_one_winner() {
+    let trie = Arc::new(AtomicContiguousTrie::<u32, u32>::new(16, 8, 1 << 17));
+    let handles: Vec<_> = (0..4u32).map(|t| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            (0..65536u32).filter(|&key| trie.try_insert(t, key).is_ok()).count()
+        })
+    }).collect();
+    let won: usize = handles.into_iter().map(|handle| handle.join().unwrap()).sum();
+    assert_eq!(won, 65536);
+    assert_eq!(trie.len(), 65536);
+}
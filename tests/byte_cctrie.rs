@@ -0,0 +1,75 @@
+extern crate cchamt;
+
+use cchamt::{ByteContiguousTrie, TrieError};
+
+#[test]
+fn test_insert_and_get() {
+    let mut trie = ByteContiguousTrie::<usize>::new();
+    let words = ["", "a", "ab", "abc", "b", "src/lib.rs", "src/cchamt.rs"];
+
+    for (i, w) in words.iter().enumerate() {
+        assert_eq!(trie.insert(i, w), None);
+    }
+    for (i, w) in words.iter().enumerate() {
+        assert_eq!(trie.get(w), Some(i));
+    }
+    assert_eq!(trie.len(), words.len());
+    assert_eq!(trie.get("abcd"), None);
+    assert_eq!(trie.get("src"), None);
+    assert!(!trie.contain("ac"));
+
+    assert_eq!(trie.insert(10, "ab"), Some(2));
+    assert_eq!(trie.try_insert(11, "ab"), Err(TrieError::Duplicate));
+    assert_eq!(trie.get(&b"ab"[..]), Some(10));
+    assert_eq!(trie.len(), words.len());
+}
+
+#[test]
+fn test_binary_keys() {
+    let mut trie = ByteContiguousTrie::<u32>::new();
+    for i in 0..1000u32 {
+        let bytes = [(i >> 8) as u8, 0, i as u8, 0xff];
+        trie.insert(i, &bytes[..]);
+    }
+    for i in 0..1000u32 {
+        let bytes = [(i >> 8) as u8, 0, i as u8, 0xff];
+        assert_eq!(trie.get(&bytes[..]), Some(i));
+    }
+    assert_eq!(trie.get(&[0u8, 0, 0][..]), None);
+}
+
+#[test]
+fn test_iter_in_lexicographic_order() {
+    let mut trie = ByteContiguousTrie::<usize>::new();
+    let words = vec!["pear", "apple", "app", "banana", "band", "", "ban", "zebra"];
+    for (i, w) in words.iter().enumerate() {
+        trie.insert(i, w);
+    }
+    let expected: Vec<(Vec<u8>, usize)> = {
+        let mut pairs: Vec<(Vec<u8>, usize)> =
+            words.iter().enumerate().map(|(i, w)| (w.as_bytes().to_vec(), i)).collect();
+        pairs.sort();
+        pairs
+    };
+    assert_eq!(trie.iter().collect::<Vec<_>>(), expected);
+
+    let keys: Vec<Vec<u8>> = trie.prefix("ban").map(|(k, _)| k).collect();
+    assert_eq!(keys, vec![b"ban".to_vec(), b"banana".to_vec(), b"band".to_vec()]);
+    assert_eq!(trie.prefix("app").count(), 2);
+    assert_eq!(trie.prefix("c").count(), 0);
+    assert_eq!(trie.prefix("").count(), words.len());
+}
+
+#[test]
+fn test_remove() {
+    let mut trie = ByteContiguousTrie::<usize>::new();
+    trie.insert(1, "ab");
+    trie.insert(2, "abc");
+
+    assert_eq!(trie.remove("a"), None);
+    assert_eq!(trie.remove("ab"), Some(1));
+    assert_eq!(trie.remove("ab"), None);
+    assert_eq!(trie.get("abc"), Some(2));
+    assert_eq!(trie.len(), 1);
+    assert_eq!(trie.iter().collect::<Vec<_>>(), vec![(b"abc".to_vec(), 2)]);
+}
@@ -1,64 +1,342 @@
 #![feature(test)]
 
-#[macro_use]
 extern crate cchamt;
 
 extern crate test;
 extern crate rand;
 
 use std::usize;
-use cchamt::{ContiguousTrie};
+use cchamt::{ContiguousTrie, TrieError};
 
 #[test]
 fn test_new_trie() {
-    let _trie = ContiguousTrie::<(usize)>::new(32,8);
+    let _trie = ContiguousTrie::<u32, usize>::new(32,8);
 }
 
 #[test]
 fn test_insert() {
-    let mut trie = ContiguousTrie::<usize>::new(32, 8);
+    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 
-    let arr = binary_format!(4).to_owned().into_bytes();
-    trie.insert(4, &arr[2..]);
+    trie.insert(4, 4).unwrap();
 
-    assert_eq!(trie.contain(&arr[2..]), true);
-    assert_eq!(trie.get(&arr[2..]).unwrap(), 4);
+    assert_eq!(trie.contain(4), true);
+    assert_eq!(trie.contain(5), false);
+    assert_eq!(trie.get(4), Ok(Some(4)));
 }
 
 #[test]
 fn test_1e5_insert() {
-    let mut trie = ContiguousTrie::<usize>::new(32, 8);
+    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 
     for i in 0..100000 {
-        let str = binary_format!(i);
-//        println!("{}", str);
-        let arr = str.to_owned().into_bytes();
-        trie.insert(i, &arr[2..]);
+        trie.insert(i as usize, i).unwrap();
     }
 
     for i in 0..100000 {
-        let str = binary_format!(i);
-        let arr = str.to_owned().into_bytes();
-        assert_eq!(trie.get(&arr[2..]).unwrap(), i);
+        assert_eq!(trie.get(i), Ok(Some(i as usize)));
     }
 }
 
 #[test]
 fn test_2pow24_insert() {
-    let mut trie = ContiguousTrie::<usize>::new(32, 8);
+    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
 
-    let tot: usize = 2usize.pow(24);
+    let tot: u32 = 2u32.pow(24);
 
     for i in 0..tot {
-        let str = binary_format!(i);
-//        println!("{}", str);
-        let arr = str.to_owned().into_bytes();
-        trie.insert(i, &arr[2..]);
+        trie.insert(i as usize, i).unwrap();
     }
 
     for i in 0..tot {
-        let str = binary_format!(i);
-        let arr = str.to_owned().into_bytes();
-        assert_eq!(trie.get(&arr[2..]).unwrap(), i);
+        assert_eq!(trie.get(i), Ok(Some(i as usize)));
     }
 }
+
+#[test]
+fn test_u16_keys() {
+    let mut trie = ContiguousTrie::<u16, u16>::new(16, 4);
+
+    for i in 0..u16::max_value() {
+        trie.insert(!i, i).unwrap();
+    }
+
+    for i in 0..u16::max_value() {
+        assert_eq!(trie.get(i), Ok(Some(!i)));
+    }
+    assert_eq!(trie.get(u16::max_value()), Ok(None));
+}
+
+#[test]
+fn test_u64_keys_use_low_bits() {
+    let mut trie = ContiguousTrie::<u64, u64>::new(24, 8);
+
+    let keys = [0u64, 1, 0xff, 0x100, 0xab_cdef, 0xff_ffff];
+    for &k in keys.iter() {
+        trie.insert(k * 2, k).unwrap();
+    }
+
+    for &k in keys.iter() {
+        assert_eq!(trie.get(k), Ok(Some(k * 2)));
+    }
+    assert!(!trie.contain(0x12_3456));
+}
+
+#[test]
+fn test_blocks_are_allocated_on_demand() {
+    let mut trie = ContiguousTrie::<u64, u64>::new(64, 8);
+    assert_eq!(trie.allocated_slots(), 256);
+
+    // the first key allocates one block per level below the root
+    trie.insert(1, 0x0102_0304_0506_0708).unwrap();
+    assert_eq!(trie.allocated_slots(), 256 * 8);
+
+    // a sibling leaf shares every block
+    trie.insert(2, 0x0102_0304_0506_0709).unwrap();
+    assert_eq!(trie.allocated_slots(), 256 * 8);
+
+    // diverging at the last interior level needs a single new block
+    trie.insert(3, 0x0102_0304_0506_0808).unwrap();
+    assert_eq!(trie.allocated_slots(), 256 * 9);
+
+    assert_eq!(trie.get(0x0102_0304_0506_0708), Ok(Some(1)));
+    assert_eq!(trie.get(0x0102_0304_0506_0709), Ok(Some(2)));
+    assert_eq!(trie.get(0x0102_0304_0506_0808), Ok(Some(3)));
+    assert_eq!(trie.get(0x0102_0304_0506_0807), Ok(None));
+    assert_eq!(trie.get(0xff02_0304_0506_0708), Ok(None));
+    assert_eq!(trie.allocated_slots(), 256 * 9);
+}
+
+#[test]
+fn test_insert_returns_previous_value() {
+    let mut trie = ContiguousTrie::<u32, usize>::new(32, 8);
+
+    assert_eq!(trie.insert(1, 42), Ok(None));
+    assert_eq!(trie.insert(2, 42), Ok(Some(1)));
+    assert_eq!(trie.get(42), Ok(Some(2)));
+
+    assert_eq!(trie.try_insert(3, 42), Err(TrieError::Duplicate));
+    assert_eq!(trie.try_insert(3, 43), Ok(()));
+    assert_eq!(trie.get(42), Ok(Some(2)));
+    assert_eq!(trie.get(43), Ok(Some(3)));
+}
+
+#[test]
+fn test_out_of_range_key() {
+    let mut trie = ContiguousTrie::<u32, usize>::new(16, 8);
+
+    assert_eq!(trie.insert(1, 0xffff), Ok(None));
+    assert_eq!(trie.insert(1, 0x1_0000), Err(TrieError::OutOfRange));
+    assert_eq!(trie.try_insert(1, 0x1_0000), Err(TrieError::OutOfRange));
+    assert_eq!(trie.get(0x1_ffff), Err(TrieError::OutOfRange));
+    assert!(!trie.contain(0x1_ffff));
+}
+
+#[test]
+fn test_iter_in_key_order() {
+    let mut trie = ContiguousTrie::<u32, u32>::new(32, 8);
+    let mut keys: Vec<u32> = (0..2000u32).map(|i| i.wrapping_mul(2654435761)).collect();
+    for &k in keys.iter() {
+        trie.insert(!k, k).unwrap();
+    }
+    keys.sort();
+
+    let pairs: Vec<(u32, u32)> = trie.iter().collect();
+    assert_eq!(pairs.len(), keys.len());
+    for (&(k, v), &expected) in pairs.iter().zip(keys.iter()) {
+        assert_eq!(k, expected);
+        assert_eq!(v, !expected);
+    }
+
+    assert_eq!(trie.keys().collect::<Vec<u32>>(), keys);
+    assert_eq!(trie.values().collect::<Vec<u32>>(), keys.iter().map(|k| !k).collect::<Vec<u32>>());
+
+    let mut reversed = keys.clone();
+    reversed.reverse();
+    assert_eq!(trie.keys().rev().collect::<Vec<u32>>(), reversed);
+}
+
+#[test]
+fn test_iter_dense_2_power_16() {
+    // every key of a 16-bit trie, inserted in scattered order so that the blocks are out of key order
+    let mut trie = ContiguousTrie::<u32, u32>::new(16, 4);
+    for i in 0..65536u32 {
+        let k = i.wrapping_mul(40503) & 0xffff;
+        trie.insert(k * 2, k).unwrap();
+    }
+    assert!(trie.keys().eq(0..65536u32));
+    assert!(trie.values().eq((0..65536u32).map(|k| k * 2)));
+    assert!(trie.keys().rev().eq((0..65536u32).rev()));
+    assert_eq!(trie.range(1000..=60000).count(), 59001);
+}
+
+#[test]
+fn test_iter_from_both_ends() {
+    let mut trie = ContiguousTrie::<u64, u64>::new(64, 16);
+    for &k in [0u64, 1, 0xffff, 1 << 40, u64::max_value()].iter() {
+        trie.insert(k, k).unwrap();
+    }
+
+    let mut iter = trie.keys();
+    assert_eq!(iter.next(), Some(0));
+    assert_eq!(iter.next_back(), Some(u64::max_value()));
+    assert_eq!(iter.next(), Some(1));
+    assert_eq!(iter.next_back(), Some(1 << 40));
+    assert_eq!(iter.next(), Some(0xffff));
+    assert_eq!(iter.next_back(), None);
+    assert_eq!(iter.next(), None);
+
+    let empty = ContiguousTrie::<u16, u16>::new(16, 8);
+    assert_eq!(empty.iter().next(), None);
+}
+
+#[test]
+fn test_range() {
+    let mut trie = ContiguousTrie::<u32, u32>::new(32, 8);
+    for i in 0..1000u32 {
+        trie.insert(i, i * 1000).unwrap();
+    }
+
+    let keys: Vec<u32> = trie.range(1500..=12000).map(|(k, _)| k).collect();
+    assert_eq!(keys, (2..=12).map(|i| i * 1000).collect::<Vec<u32>>());
+
+    let keys: Vec<u32> = trie.range(2000..12000).map(|(k, _)| k).collect();
+    assert_eq!(keys, (2..12).map(|i| i * 1000).collect::<Vec<u32>>());
+
+    let values: Vec<u32> = trie.range(..3000).rev().map(|(_, v)| v).collect();
+    assert_eq!(values, vec![2, 1, 0]);
+
+    assert_eq!(trie.range(998_500..).count(), 1);
+    assert_eq!(trie.range(1001..1999).count(), 0);
+    assert_eq!(trie.range(5000..5000).count(), 0);
+}
+
+#[test]
+fn test_range_from_both_ends() {
+    let mut trie = ContiguousTrie::<u32, u32>::new(32, 8);
+    for i in 0..1024u32 {
+        trie.insert(i, i).unwrap();
+    }
+
+    // both ends within one leaf block
+    let mut iter = trie.range(10..=13).map(|(k, _)| k);
+    assert_eq!(iter.next_back(), Some(13));
+    assert_eq!(iter.next(), Some(10));
+    assert_eq!(iter.next_back(), Some(12));
+    assert_eq!(iter.next_back(), Some(11));
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+
+    // the ends meet in the middle leaf block of three
+    let mut iter = trie.range(250..=520).map(|(k, _)| k);
+    let front: Vec<u32> = iter.by_ref().take(136).collect();
+    let back: Vec<u32> = iter.rev().collect();
+    assert_eq!(front, (250..386).collect::<Vec<u32>>());
+    assert_eq!(back, (386..=520).rev().collect::<Vec<u32>>());
+}
+
+#[test]
+fn test_prefix() {
+    let mut trie = ContiguousTrie::<u32, u32>::new(32, 8);
+    // a few addresses in 10.1.0.0/16, 10.2.0.0/16 and 192.168.0.0/16
+    let addrs = [0x0a01_0001u32, 0x0a01_0203, 0x0a01_ffff, 0x0a02_0000, 0xc0a8_0001];
+    for (i, &a) in addrs.iter().enumerate() {
+        trie.insert(i as u32, a).unwrap();
+    }
+
+    let in_10_1: Vec<u32> = trie.prefix(0x0a01, 16).map(|(k, _)| k).collect();
+    assert_eq!(in_10_1, vec![0x0a01_0001, 0x0a01_0203, 0x0a01_ffff]);
+    assert_eq!(trie.prefix(0x0a, 8).count(), 4);
+    assert_eq!(trie.prefix(0xc0a8_0001, 32).collect::<Vec<(u32, u32)>>(), vec![(0xc0a8_0001, 4)]);
+    assert_eq!(trie.prefix(0, 0).count(), addrs.len());
+    assert_eq!(trie.prefix(0x0a03, 16).count(), 0);
+    assert_eq!(trie.prefix(0x1_0000, 16).count(), 0);
+}
+
+#[test]
+fn test_full_length_prefix_of_64_bit_keys() {
+    let mut trie = ContiguousTrie::<u64, u64>::new(64, 8);
+    let k = 0xdead_beef_0123_4567u64;
+    for &key in [k - 1, k, k + 1, u64::max_value()].iter() {
+        trie.insert(key ^ 1, key).unwrap();
+    }
+
+    assert_eq!(trie.prefix(k, 64).collect::<Vec<(u64, u64)>>(), vec![(k, k ^ 1)]);
+    assert_eq!(trie.prefix(u64::max_value(), 64).count(), 1);
+    assert_eq!(trie.prefix(k + 2, 64).count(), 0);
+    assert_eq!(trie.prefix(k >> 8, 56).count(), 3);
+}
+
+#[test]
+fn test_remove_and_shrink() {
+    let mut trie = ContiguousTrie::<u32, u32>::new(32, 8);
+    for i in 0..1000u32 {
+        trie.insert(i, i << 16).unwrap();
+    }
+    let allocated = trie.allocated_slots();
+
+    assert_eq!(trie.remove(5 << 16), Ok(Some(5)));
+    assert_eq!(trie.remove(5 << 16), Ok(None));
+    assert_eq!(trie.remove(0xdead_beef), Ok(None));
+    assert_eq!(trie.remove(0), Ok(Some(0)));
+    assert_eq!(trie.get(5 << 16), Ok(None));
+    assert!(!trie.contain(5 << 16));
+    assert_eq!(trie.allocated_slots(), allocated);
+
+    // each key had blocks of its own below the root's children, removing them frees those blocks
+    trie.shrink_to_fit();
+    assert!(trie.allocated_slots() < allocated);
+
+    let keys: Vec<u32> = trie.keys().collect();
+    assert_eq!(keys.len(), 998);
+    for i in 1..1000u32 {
+        if i != 5 {
+            assert_eq!(trie.get(i << 16), Ok(Some(i)));
+        }
+    }
+    trie.insert(5, 5 << 16).unwrap();
+    assert_eq!(trie.get(5 << 16), Ok(Some(5)));
+
+    for i in 0..1000u32 {
+        trie.remove(i << 16).unwrap();
+    }
+    trie.shrink_to_fit();
+    assert_eq!(trie.allocated_slots(), 256);
+    assert_eq!(trie.iter().next(), None);
+}
+
+#[test]
+fn test_segment_schedule() {
+    let mut wide_root = ContiguousTrie::<u32, u32>::with_segments(&[16, 8, 8]);
+    let mut uneven = ContiguousTrie::<u32, u32>::with_segments(&[12, 10, 10]);
+    assert_eq!(wide_root.allocated_slots(), 1 << 16);
+    assert_eq!(uneven.allocated_slots(), 1 << 12);
+
+    let keys: Vec<u32> = (0..5000u32).map(|i| i.wrapping_mul(2654435761)).collect();
+    for &k in keys.iter() {
+        wide_root.insert(k, k).unwrap();
+        uneven.insert(k, k).unwrap();
+    }
+    for &k in keys.iter() {
+        assert_eq!(wide_root.get(k), Ok(Some(k)));
+        assert_eq!(uneven.get(k), Ok(Some(k)));
+    }
+    assert_eq!(wide_root.get(1), Ok(None));
+
+    let mut sorted = keys.clone();
+    sorted.sort();
+    assert_eq!(wide_root.keys().collect::<Vec<u32>>(), sorted);
+    assert_eq!(uneven.keys().rev().collect::<Vec<u32>>(), sorted.iter().rev().cloned().collect::<Vec<u32>>());
+    assert_eq!(uneven.prefix(sorted[0] >> 20, 12).next(), Some((sorted[0], sorted[0])));
+}
+
+#[test]
+fn test_segment_schedule_blocks() {
+    // a root of 2^4 slots, then a block of 2^2 and a leaf block of 2^6
+    let mut trie = ContiguousTrie::<u16, u16>::with_segments(&[4, 2, 6]);
+    trie.insert(1, 0b1010_11_000001).unwrap();
+    assert_eq!(trie.allocated_slots(), 16 + 4 + 64);
+    trie.insert(2, 0b1010_10_000001).unwrap();
+    assert_eq!(trie.allocated_slots(), 16 + 4 + 64 + 64);
+    assert_eq!(trie.keys().collect::<Vec<u16>>(), vec![0b1010_10_000001, 0b1010_11_000001]);
+    assert_eq!(trie.insert(3, 0b1_0000_0000_0000), Err(TrieError::OutOfRange));
+}
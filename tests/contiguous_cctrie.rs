@@ -11,7 +11,9 @@ use std::usize;
 //use std::collections::HashMap;
 //use rand::{Rng, thread_rng};
 //use rand::Rng;
-use cchamt::MutexContiguousTrie;
+use std::sync::Arc;
+use std::thread;
+use cchamt::{MutexContiguousTrie, TrieError};
 
 
 #[test]
@@ -26,13 +28,13 @@ fn test_2_power_16_insert() {
     for i in 0..65536 {
         let str = binary_format!(i);
         let arr = str.to_owned().into_bytes();
-        trie.insert(i, &arr[2..]);
+        trie.insert(i, &arr[2..]).unwrap();
     }
 
     for i in 0..65536 {
         let str = binary_format!(i);
         let arr = str.to_owned().into_bytes();
-        assert_eq!(trie.get(&arr[2..]).unwrap(), i);
+        assert_eq!(trie.get(&arr[2..]), Ok(Some(i)));
     }
 }
 
@@ -43,12 +45,93 @@ fn test_million_consecutive_insert() {
     for i in 0..1000000 {
         let str = binary_format!(i);
         let arr = str.to_owned().into_bytes();
-        trie.insert(i, &arr[2..]);
+        trie.insert(i, &arr[2..]).unwrap();
     }
 
     for i in 0..1000000 {
         let str = binary_format!(i);
         let arr = str.to_owned().into_bytes();
-        assert_eq!(trie.get(&arr[2..]).unwrap(), i);
+        assert_eq!(trie.get(&arr[2..]), Ok(Some(i)));
+    }
+}
+
+#[test]
+fn test_insert_returns_previous_value() {
+    let trie = MutexContiguousTrie::<usize>::new(32, 8);
+    let arr = binary_format!(42).to_owned().into_bytes();
+
+    assert_eq!(trie.insert(1, &arr[2..]), Ok(None));
+    assert_eq!(trie.insert(2, &arr[2..]), Ok(Some(1)));
+    assert_eq!(trie.try_insert(3, &arr[2..]), Err(TrieError::Duplicate));
+    assert_eq!(trie.get(&arr[2..]), Ok(Some(2)));
+}
+
+#[test]
+fn test_malformed_keys_are_rejected() {
+    let trie = MutexContiguousTrie::<usize>::new(32, 8);
+    let arr = binary_format!(42).to_owned().into_bytes();
+
+    assert_eq!(trie.insert(1, &arr[3..]), Err(TrieError::WrongLength));
+    assert_eq!(trie.insert(1, &arr[..32]), Err(TrieError::InvalidDigit)); // starts with "0b"
+    assert_eq!(trie.insert(1, "0000000000000000000000000000002a".as_bytes()), Err(TrieError::InvalidDigit));
+    assert_eq!(trie.get(&arr[3..]), Err(TrieError::WrongLength));
+    assert!(!trie.contain(&arr[..32]));
+    assert_eq!(trie.get(&arr[2..]), Ok(None));
+}
+
+#[test]
+fn test_remove_and_shrink() {
+    let trie = MutexContiguousTrie::<usize>::new(16, 8);
+    let interior = trie.allocated_slots();
+
+    for i in 0..1000 {
+        let arr = format!("{:016b}", i).into_bytes();
+        trie.insert(i, &arr).unwrap();
+    }
+    for i in 500..1000 {
+        let arr = format!("{:016b}", i).into_bytes();
+        assert_eq!(trie.remove(&arr), Ok(Some(i)));
+        assert_eq!(trie.remove(&arr), Ok(None));
+    }
+    let arr = format!("{:016b}", 499).into_bytes();
+    assert_eq!(trie.get(&arr), Ok(Some(499)));
+
+    let grown = trie.allocated_slots();
+    trie.shrink_to_fit();
+    assert!(trie.allocated_slots() < grown);
+    assert!(trie.allocated_slots() > interior);
+    assert_eq!(trie.get(&arr), Ok(Some(499)));
+}
+
+#[test]
+fn test_racing_try_insert_has_one_winner() {
+    let trie = Arc::new(MutexContiguousTrie::<usize>::new(16, 8));
+    let handles: Vec<_> = (0..8).map(|t| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            // every thread goes through the same keys, from a different starting point
+            let mut won = vec![];
+            for j in 0..2048 {
+                let i = (j + t * 256) % 2048;
+                let arr = format!("{:016b}", i).into_bytes();
+                if trie.try_insert(t, &arr).is_ok() {
+                    won.push(i);
+                }
+            }
+            (t, won)
+        })
+    }).collect();
+
+    let mut winners = vec![None; 2048];
+    for handle in handles {
+        let (t, won) = handle.join().unwrap();
+        for i in won {
+            assert_eq!(winners[i], None, "key {} won twice", i);
+            winners[i] = Some(t);
+        }
+    }
+    for i in 0..2048 {
+        let arr = format!("{:016b}", i).into_bytes();
+        assert_eq!(trie.get(&arr), Ok(winners[i]));
     }
 }
@@ -0,0 +1,92 @@
+extern crate cchamt;
+
+use std::sync::Arc;
+use std::thread;
+use cchamt::{ContiguousTrie, FrozenContiguousTrie, TrieError};
+
+fn scattered_keys(n: u32) -> Vec<u32> {
+    (0..n).map(|i| i.wrapping_mul(2654435761)).collect()
+}
+
+#[test]
+fn test_freeze_keeps_entries() {
+    let mut trie = ContiguousTrie::<u32, u32>::new(32, 8);
+    let keys = scattered_keys(10000);
+    for &k in keys.iter() {
+        trie.insert(!k, k).unwrap();
+    }
+    trie.remove(keys[0]).unwrap();
+
+    let frozen = trie.freeze();
+    assert_eq!(frozen.len(), keys.len() - 1);
+    assert_eq!(frozen.get(keys[0]), Ok(None));
+    for &k in keys[1..].iter() {
+        assert_eq!(frozen.get(k), Ok(Some(!k)));
+    }
+    assert!(!frozen.contain(keys[0] ^ 1));
+
+    let mut sorted = keys[1..].to_vec();
+    sorted.sort();
+    let pairs: Vec<(u32, u32)> = frozen.iter().collect();
+    assert_eq!(pairs, sorted.iter().map(|&k| (k, !k)).collect::<Vec<(u32, u32)>>());
+    assert_eq!(frozen.iter().rev().next(), Some((sorted[sorted.len() - 1], !sorted[sorted.len() - 1])));
+}
+
+#[test]
+fn test_freeze_segment_schedule() {
+    let mut trie = ContiguousTrie::<u64, u64>::with_segments(&[12, 2, 10]);
+    for k in 0..3000u64 {
+        trie.insert(k, k * 4099).unwrap();
+    }
+
+    let frozen = trie.freeze();
+    for k in 0..3000u64 {
+        assert_eq!(frozen.get(k * 4099), Ok(Some(k)));
+    }
+    assert_eq!(frozen.get(1 << 24), Err(TrieError::OutOfRange));
+    assert_eq!(frozen.iter().map(|(k, _)| k).collect::<Vec<u64>>(),
+               (0..3000u64).map(|k| k * 4099).collect::<Vec<u64>>());
+}
+
+#[test]
+fn test_frozen_is_smaller() {
+    let mut trie = ContiguousTrie::<u32, u32>::new(32, 8);
+    for i in 0..100000u32 {
+        trie.insert(i, i).unwrap();
+    }
+    let before = trie.size_in_bytes();
+    let frozen = trie.freeze();
+    // the values alone take 4 bytes each, the bitmaps a bit each for every slot
+    assert!(frozen.size_in_bytes() * 4 < before);
+    assert!(frozen.size_in_bytes() < 100000 * 4 + 100000);
+}
+
+#[test]
+fn test_empty_freeze() {
+    let frozen = ContiguousTrie::<u16, u16>::new(16, 4).freeze();
+    assert!(frozen.is_empty());
+    assert_eq!(frozen.get(7), Ok(None));
+    assert_eq!(frozen.iter().next(), None);
+}
+
+#[test]
+fn test_shared_between_readers() {
+    let mut trie = ContiguousTrie::<u32, u32>::new(32, 8);
+    for i in 0..100000u32 {
+        trie.insert(i, i).unwrap();
+    }
+    let frozen: Arc<FrozenContiguousTrie<u32, u32>> = Arc::new(trie.freeze());
+
+    let mut thread_handle: Vec<thread::JoinHandle<_>> = vec![];
+    for t_id in 0..4u32 {
+        let thread_trie = frozen.clone();
+        thread_handle.push(thread::spawn(move || {
+            for i in (t_id * 25000)..((t_id + 1) * 25000) {
+                assert_eq!(thread_trie.get(i), Ok(Some(i)));
+            }
+        }));
+    }
+    for thread in thread_handle {
+        thread.join().unwrap();
+    }
+}
@@ -10,13 +10,27 @@ extern crate rand;
 use std::usize;
 //use std::collections::HashMap;
 //use rand::{Rng, thread_rng};
-use cchamt::LockfreeTrie;
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use cchamt::{LockfreeTrie, Allocator, BoxAllocator, MmapAllocator, DebugAllocator, AllocError, Node, ANode};
+use cchamt::{NodeAllocator, Region, Usage};
 
 #[test]
 fn test_lockfree_new_lockfree_trie() {
     let _trie = LockfreeTrie::<u64,usize>::new();
 }
 
+#[test]
+fn test_lockfree_node_layout() {
+    // an SNode is hash, key, value and txn pointer behind the tag; the array slots live apart
+    // from the node, so no variant is larger than that
+    assert_eq!(mem::size_of::<Node<u64, u64>>(), 5 * mem::size_of::<u64>());
+    assert_eq!(mem::align_of::<Node<u64, u64>>(), mem::align_of::<u64>());
+    assert_eq!(mem::size_of::<ANode<u64, u64>>(), 2 * mem::size_of::<usize>());
+    assert_eq!(mem::align_of::<ANode<u64, u64>>(), mem::align_of::<usize>());
+}
+
 #[test]
 fn test_lockfree_2_power_16_insert() {
     let mut trie = LockfreeTrie::<u64,u64>::new();
@@ -50,3 +64,295 @@ fn test_lockfree_million_consecutive_insert() {
         }
     }
 }
+
+#[test]
+fn test_lockfree_ordered_lookup() {
+    let mut trie = LockfreeTrie::<u64, u64>::new_ordered();
+
+    for i in 0..65536 {
+        trie.insert(i, i+1);
+    }
+
+    for i in 0..65536 {
+        assert_eq!(trie.lookup(&i), Some(&(i+1)));
+    }
+    assert_eq!(trie.lookup(&65536), None);
+}
+
+#[test]
+fn test_lockfree_ordered_range() {
+    let mut trie = LockfreeTrie::<u32, u32>::new_ordered();
+
+    // insert in a scrambled order so the layout doesn't follow the insertion order
+    for i in 0..10000u32 {
+        let k = (i * 7919) % 10000 * 3;
+        trie.insert(k, k / 3);
+    }
+
+    let found: Vec<(u32, u32)> = trie.range(300..600).collect();
+    let expected: Vec<(u32, u32)> = (100..200).map(|i| (i * 3, i)).collect();
+    assert_eq!(found, expected);
+
+    let found: Vec<(u32, u32)> = trie.range(299..=601).collect();
+    assert_eq!(found.len(), 101);
+    assert_eq!(found[0], (300, 100));
+    assert_eq!(found[100], (600, 200));
+
+    assert_eq!(trie.range(1..3).count(), 0);
+    assert_eq!(trie.range(..).count(), 10000);
+}
+
+#[test]
+fn test_lockfree_ordered_iter_from() {
+    let mut trie = LockfreeTrie::<i64, i64>::new_ordered();
+
+    for i in -500..500 {
+        trie.insert(i * 1000, i);
+    }
+
+    let found: Vec<i64> = trie.iter_from(-2500).map(|(_, v)| v).collect();
+    let expected: Vec<i64> = (-2..500).collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn test_lockfree_get_many() {
+    let mut trie = LockfreeTrie::<u64, u64>::new();
+
+    for i in 0..65536 {
+        trie.insert(i, i+1);
+    }
+
+    let keys: Vec<u64> = (0..512).map(|i| (i * 131) % 70000).collect();
+    let values = trie.get_many(&keys);
+    assert_eq!(values.len(), keys.len());
+    for (key, value) in keys.iter().zip(values) {
+        if *key < 65536 {
+            assert_eq!(value, Some(key + 1));
+        } else {
+            assert_eq!(value, None);
+        }
+    }
+
+    assert!(trie.get_many(&[]).is_empty());
+}
+
+#[test]
+fn test_lockfree_overwrite() {
+    let mut trie = LockfreeTrie::<usize, usize>::new();
+
+    for round in 0..50 {
+        for i in 0..10000 {
+            trie.insert(i, i + round);
+        }
+    }
+
+    for i in 0..10000 {
+        assert_eq!(trie.lookup(&i), Some(&(i + 49)));
+    }
+}
+
+#[test]
+fn test_lockfree_repack() {
+    let mut trie = LockfreeTrie::<usize, usize>::new();
+    let range = 100000;
+
+    for i in 0..range {
+        trie.insert(i, i + 1);
+    }
+
+    assert_eq!(trie.repack(1024), 0); // nothing sampled yet
+
+    // lookups are only sampled while profiling
+    for i in 0..range {
+        assert_eq!(trie.lookup(&i), Some(&(i + 1)));
+    }
+    assert_eq!(trie.repack(1024), 0);
+
+    // a small set of hot keys
+    trie.start_profiling();
+    for _ in 0..1000 {
+        for i in 0..64 {
+            assert_eq!(trie.lookup(&(i * 997)), Some(&(i * 997 + 1)));
+        }
+    }
+
+    trie.stop_profiling();
+    let moved = trie.repack(1024);
+    assert!(moved > 0 && moved <= 1024);
+    for i in 0..range {
+        assert_eq!(trie.lookup(&i), Some(&(i + 1)));
+    }
+
+    // the old nodes are recycled by the next inserts
+    for i in range..2 * range {
+        trie.insert(i, i + 1);
+    }
+    for i in 0..2 * range {
+        assert_eq!(trie.lookup(&i), Some(&(i + 1)));
+    }
+}
+
+// DebugAllocator whose try_alloc fails once it has handed out limit nodes, so that an insert
+// can be stopped halfway; regions are not limited
+struct LimitedAllocator {
+    mem: DebugAllocator<Node<u64, u64>>,
+    limit: Arc<AtomicUsize>,
+}
+
+impl NodeAllocator<Node<u64, u64>> for LimitedAllocator {
+    fn try_alloc(&self, obj: Node<u64, u64>) -> Result<&mut Node<u64, u64>, AllocError> {
+        if self.limit.load(Ordering::Relaxed) == 0 {
+            return Err(AllocError::CapacityExceeded);
+        }
+        self.limit.fetch_sub(1, Ordering::Relaxed);
+        self.mem.try_alloc(obj)
+    }
+    fn dealloc(&self, obj: &mut Node<u64, u64>) { self.mem.dealloc(obj) }
+    fn retire(&self, obj: &mut Node<u64, u64>) { self.mem.retire(obj) }
+    fn reclaim(&mut self) { self.mem.reclaim() }
+    fn region(&self, len: usize) -> Option<Region<Node<u64, u64>>> { self.mem.region(len) }
+    fn validate(&self, obj: &Node<u64, u64>) { self.mem.validate(obj) }
+    fn usage(&self) -> Usage { self.mem.usage() }
+    unsafe fn for_each_live<F: FnMut(&Node<u64, u64>)>(&self, f: F) { self.mem.for_each_live(f) }
+}
+
+#[test]
+fn test_lockfree_repack_around_pending_expansion() {
+    let limit = Arc::new(AtomicUsize::new(usize::MAX));
+    let mut trie = LockfreeTrie::<u64, u64, _>::with_allocator(LimitedAllocator {
+        mem: DebugAllocator::new(1 << 20),
+        limit: limit.clone(),
+    });
+    let range = 20000;
+    for i in 0..range {
+        trie.insert(i, i + 1);
+    }
+
+    // stop inserts partway through until one leaves an expansion node behind
+    let mut key = range;
+    let mut attempt = 0;
+    while trie.memory_usage().reachable.enode == 0 {
+        limit.store(attempt % 8, Ordering::Relaxed);
+        if trie.try_insert(key, key + 1).is_ok() {
+            key += 1;
+        }
+        attempt += 1;
+    }
+    limit.store(usize::MAX, Ordering::Relaxed);
+
+    // the key that failed goes through the array node that is being expanded, make it hot
+    trie.start_profiling();
+    for _ in 0..10000 {
+        assert_eq!(trie.lookup(&key), None);
+    }
+    trie.stop_profiling();
+    assert!(trie.repack(1 << 16) > 0);
+
+    // the next inserts finish the expansion
+    for i in key..key + range {
+        trie.insert(i, i + 1);
+    }
+    assert_eq!(trie.memory_usage().reachable.enode, 0);
+    for i in 0..key + range {
+        assert_eq!(trie.lookup(&i), Some(&(i + 1)));
+    }
+}
+
+// overwrites every key a few times, so nodes are freed and recycled, then reads them all back
+macro_rules! overwrite_and_check {
+    ($trie:expr) => {{
+        let mut trie = $trie;
+        for round in 0..5 {
+            for i in 0..20000usize {
+                trie.insert(i, i + round);
+            }
+        }
+        for i in 0..20000 {
+            assert_eq!(trie.lookup(&i), Some(&(i + 4)));
+        }
+        let keys: Vec<usize> = (0..20000).collect();
+        for (i, value) in trie.get_many(&keys).into_iter().enumerate() {
+            assert_eq!(value, Some(i + 4));
+        }
+    }};
+}
+
+#[test]
+fn test_lockfree_allocator_backends() {
+    overwrite_and_check!(LockfreeTrie::<usize, usize, _>::with_allocator(BoxAllocator::new()));
+    overwrite_and_check!(LockfreeTrie::<usize, usize, _>::with_allocator(MmapAllocator::new(1 << 20)));
+    // validate panics if a freed node is still reachable
+    overwrite_and_check!(LockfreeTrie::<usize, usize, _>::with_allocator(DebugAllocator::new(1 << 20)));
+}
+
+#[test]
+fn test_lockfree_memory_usage() {
+    let mut trie = LockfreeTrie::<usize, usize>::new();
+    for i in 0..10000 {
+        trie.insert(i, i);
+    }
+
+    let usage = trie.memory_usage();
+    assert_eq!(usage.reachable.snode, 10000);
+    assert_eq!(usage.reachable.notxn, 10000);
+    assert_eq!(usage.live.total(), usage.allocator.in_use);
+    assert!(usage.allocator.high_water >= usage.allocator.in_use);
+    assert!(usage.allocator.touched_bytes <= usage.allocator.reserved_bytes);
+    // insert reclaims everything it replaced
+    assert_eq!(usage.wasted().total(), 0);
+
+    // overwrites reuse the slots of the nodes they replace
+    for i in 0..10000 {
+        trie.insert(i, i + 1);
+    }
+    let after = trie.memory_usage();
+    assert_eq!(after.reachable, usage.reachable);
+    assert_eq!(after.allocator.in_use, usage.allocator.in_use);
+}
+
+#[test]
+fn test_lockfree_try_insert_capacity_exceeded() {
+    let mut trie = LockfreeTrie::<u64, u64, _>::with_allocator(DebugAllocator::new(3000));
+    let mut inserted = 0;
+    loop {
+        match trie.try_insert(inserted, inserted + 1) {
+            Ok(()) => inserted += 1,
+            Err(e) => {
+                assert_eq!(e, AllocError::CapacityExceeded);
+                break;
+            }
+        }
+    }
+    assert!(inserted > 100);
+
+    // everything inserted before the failure is still there, and the failed key isn't
+    for i in 0..inserted {
+        assert_eq!(trie.lookup(&i), Some(&(i + 1)));
+    }
+    assert_eq!(trie.lookup(&inserted), None);
+    assert_eq!(trie.try_insert(inserted, 0), Err(AllocError::CapacityExceeded));
+}
+
+#[test]
+fn test_lockfree_failed_overwrite_keeps_old_value() {
+    let mut trie = LockfreeTrie::<u64, u64, _>::with_allocator(Allocator::new(3000));
+    let mut inserted = 0;
+    while trie.try_insert(inserted, inserted).is_ok() {
+        inserted += 1;
+    }
+
+    // an overwrite needs new nodes before it can reclaim the old ones, so most of these fail
+    let mut failed = 0;
+    for i in 0..inserted {
+        match trie.try_insert(i, i + 1) {
+            Ok(()) => assert_eq!(trie.lookup(&i), Some(&(i + 1))),
+            Err(_) => {
+                assert_eq!(trie.lookup(&i), Some(&i));
+                failed += 1;
+            }
+        }
+    }
+    assert!(failed > 0);
+    assert_eq!(trie.memory_usage().wasted().total(), 0);
+}
@@ -0,0 +1,184 @@
+extern crate cchamt;
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use cchamt::{ContiguousTrie, MappedContiguousTrie, TrieError};
+
+// a file in the temp directory that is removed at the end of the test
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> TempFile {
+        TempFile(env::temp_dir().join(format!("cchamt-{}-{}", name, std::process::id())))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn scattered_keys(n: u32) -> Vec<u32> {
+    (0..n).map(|i| i.wrapping_mul(2654435761)).collect()
+}
+
+#[test]
+fn test_save_and_open() {
+    let file = TempFile::new("save_and_open");
+    let mut trie = ContiguousTrie::<u32, u64>::new(32, 8);
+    let keys = scattered_keys(10000);
+    for &k in keys.iter() {
+        trie.insert(k as u64 * 3, k).unwrap();
+    }
+    trie.remove(keys[0]).unwrap();
+    trie.save(&file.0).unwrap();
+
+    let mapped = MappedContiguousTrie::<u32, u64>::open(&file.0).unwrap();
+    assert_eq!(mapped.len(), keys.len() - 1);
+    assert_eq!(mapped.get(keys[0]), Ok(None));
+    for &k in keys[1..].iter() {
+        assert_eq!(mapped.get(k), Ok(Some(k as u64 * 3)));
+    }
+    assert!(!mapped.contain(keys[1] ^ 1));
+}
+
+#[test]
+fn test_saved_blocks_are_in_key_order() {
+    // the blocks keep their offsets whatever order the keys come in, so the files are the same
+    let ascending = TempFile::new("ascending");
+    let scattered = TempFile::new("scattered");
+    let mut keys = scattered_keys(5000);
+    let mut a = ContiguousTrie::<u32, u32>::new(32, 8);
+    let mut b = ContiguousTrie::<u32, u32>::new(32, 8);
+    for &k in keys.iter() {
+        b.insert(k, k).unwrap();
+    }
+    keys.sort();
+    for &k in keys.iter() {
+        a.insert(k, k).unwrap();
+    }
+    a.save(&ascending.0).unwrap();
+    b.save(&scattered.0).unwrap();
+    assert_eq!(fs::read(&ascending.0).unwrap(), fs::read(&scattered.0).unwrap());
+}
+
+#[test]
+fn test_save_segment_schedule() {
+    let file = TempFile::new("segment_schedule");
+    let mut trie = ContiguousTrie::<u64, u16>::with_segments(&[12, 2, 10]);
+    for k in 0..3000u64 {
+        trie.insert(k as u16, k * 4099).unwrap();
+    }
+    trie.save(&file.0).unwrap();
+
+    let mapped = MappedContiguousTrie::<u64, u16>::open(&file.0).unwrap();
+    for k in 0..3000u64 {
+        assert_eq!(mapped.get(k * 4099), Ok(Some(k as u16)));
+        assert_eq!(mapped.get(k * 4099 + 1), Ok(None));
+    }
+    assert_eq!(mapped.get(1 << 24), Err(TrieError::OutOfRange));
+}
+
+#[test]
+fn test_open_rejects_other_types() {
+    let file = TempFile::new("other_types");
+    let mut trie = ContiguousTrie::<u32, u64>::new(16, 8);
+    trie.insert(7, 42).unwrap();
+    trie.save(&file.0).unwrap();
+
+    let err = MappedContiguousTrie::<u32, u32>::open(&file.0).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    let err = MappedContiguousTrie::<u64, u64>::open(&file.0).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(MappedContiguousTrie::<u32, u64>::open(&file.0).is_ok());
+}
+
+#[test]
+fn test_open_rejects_damaged_files() {
+    let file = TempFile::new("damaged");
+    let mut trie = ContiguousTrie::<u32, u32>::new(16, 8);
+    for k in 0..1000u32 {
+        trie.insert(k, k).unwrap();
+    }
+    trie.save(&file.0).unwrap();
+    let length = fs::metadata(&file.0).unwrap().len();
+
+    // a flipped byte in the slots
+    {
+        let mut f = OpenOptions::new().write(true).open(&file.0).unwrap();
+        f.seek(SeekFrom::Start(length - 4)).unwrap();
+        f.write_all(&[0xff]).unwrap();
+    }
+    let err = MappedContiguousTrie::<u32, u32>::open(&file.0).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    // a truncated file
+    OpenOptions::new().write(true).open(&file.0).unwrap().set_len(length / 2).unwrap();
+    let err = MappedContiguousTrie::<u32, u32>::open(&file.0).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    // not a trie at all
+    fs::write(&file.0, b"not a trie").unwrap();
+    let err = MappedContiguousTrie::<u32, u32>::open(&file.0).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
+
+// FNV-1a over the slots, as save computes it
+fn fnv(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+#[test]
+fn test_open_rejects_bad_offsets() {
+    let file = TempFile::new("bad_offsets");
+    let mut trie = ContiguousTrie::<u32, u32>::new(16, 8);
+    trie.insert(7, 0x0102).unwrap();
+    trie.save(&file.0).unwrap();
+    let saved = fs::read(&file.0).unwrap();
+
+    // the root slot of the key points past the end of the slots, into the root block itself,
+    // and at a leaf; each file has a checksum that matches
+    let slots = (saved.len() - 128) as u64 / 16;
+    for &next in [slots, slots - 1, 0, u64::max_value() - 1].iter() {
+        let mut bytes = saved.clone();
+        bytes[128 + 16..128 + 24].copy_from_slice(&next.to_ne_bytes());
+        let hash = fnv(&bytes[128..]);
+        bytes[120..128].copy_from_slice(&hash.to_ne_bytes());
+        fs::write(&file.0, &bytes).unwrap();
+
+        let err = MappedContiguousTrie::<u32, u32>::open(&file.0).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    fs::write(&file.0, &saved).unwrap();
+    let mapped = MappedContiguousTrie::<u32, u32>::open(&file.0).unwrap();
+    assert_eq!(mapped.get(0x0102), Ok(Some(7)));
+}
+
+#[test]
+fn test_mapped_shared_between_readers() {
+    let file = TempFile::new("shared");
+    let mut trie = ContiguousTrie::<u32, u32>::new(24, 8);
+    for k in 0..100000u32 {
+        trie.insert(!k, k).unwrap();
+    }
+    trie.save(&file.0).unwrap();
+
+    let mapped = Arc::new(MappedContiguousTrie::<u32, u32>::open(&file.0).unwrap());
+    let handles: Vec<_> = (0..4u32).map(|t| {
+        let mapped = mapped.clone();
+        thread::spawn(move || {
+            for k in (t * 25000)..((t + 1) * 25000) {
+                assert_eq!(mapped.get(k), Ok(Some(!k)));
+            }
+        })
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
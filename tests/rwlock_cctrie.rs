@@ -0,0 +1,327 @@
+#![feature(test)]
+
+#[macro_use]
+extern crate cchamt;
+
+extern crate test;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use cchamt::{RwContiguousTrie, TrieError};
+
+#[test]
+fn test_2_power_16_insert() {
+    let trie = RwContiguousTrie::<usize>::new(32, 8);
+
+    for i in 0..65536 {
+        let arr = binary_format!(i).to_owned().into_bytes();
+        trie.insert(i, &arr[2..]).unwrap();
+    }
+
+    for i in 0..65536 {
+        let arr = binary_format!(i).to_owned().into_bytes();
+        assert_eq!(trie.get(&arr[2..]), Ok(Some(i)));
+    }
+}
+
+#[test]
+fn test_insert_returns_previous_value() {
+    let trie = RwContiguousTrie::<usize>::new(32, 8);
+    let arr = binary_format!(42).to_owned().into_bytes();
+
+    assert_eq!(trie.insert(1, &arr[2..]), Ok(None));
+    assert_eq!(trie.insert(2, &arr[2..]), Ok(Some(1)));
+    assert_eq!(trie.try_insert(3, &arr[2..]), Err(TrieError::Duplicate));
+    assert_eq!(trie.get(&arr[2..]), Ok(Some(2)));
+}
+
+#[test]
+fn test_malformed_keys_are_rejected() {
+    let trie = RwContiguousTrie::<usize>::new(32, 8);
+    let arr = binary_format!(42).to_owned().into_bytes();
+
+    assert_eq!(trie.insert(1, &arr[3..]), Err(TrieError::WrongLength));
+    assert_eq!(trie.try_insert(1, &arr[..32]), Err(TrieError::InvalidDigit));
+    assert_eq!(trie.get("0000000000000000000000000000002a".as_bytes()), Err(TrieError::InvalidDigit));
+    assert!(!trie.contain(&arr[3..]));
+}
+
+#[test]
+fn test_remove_and_shrink() {
+    let trie = RwContiguousTrie::<usize>::new(16, 8);
+    let interior = trie.allocated_slots();
+
+    for i in 0..1000 {
+        let arr = format!("{:016b}", i).into_bytes();
+        trie.insert(i, &arr).unwrap();
+    }
+    for i in 0..1000 {
+        let arr = format!("{:016b}", i).into_bytes();
+        assert_eq!(trie.remove(&arr), Ok(Some(i)));
+    }
+
+    trie.shrink_to_fit();
+    assert_eq!(trie.allocated_slots(), interior);
+}
+
+#[test]
+fn test_get_optimistic() {
+    let trie = RwContiguousTrie::<usize>::new(16, 8);
+    for i in 0..1000 {
+        let arr = format!("{:016b}", i).into_bytes();
+        trie.insert(i, &arr).unwrap();
+    }
+    for i in 0..2000 {
+        let arr = format!("{:016b}", i).into_bytes();
+        assert_eq!(trie.get_optimistic(&arr), trie.get(&arr));
+    }
+    assert_eq!(trie.get_optimistic("0000000000000002".as_bytes()), Err(TrieError::InvalidDigit));
+
+    let arr = format!("{:016b}", 999).into_bytes();
+    trie.remove(&arr).unwrap();
+    trie.shrink_to_fit();
+    assert_eq!(trie.get_optimistic(&arr), Ok(None));
+    assert_eq!(trie.get_optimistic(&format!("{:016b}", 998).into_bytes()), Ok(Some(998)));
+}
+
+#[test]
+fn test_small_values() {
+    // a leaf keeps the value in whole words, a narrower value takes only the first bytes of one
+    let trie = RwContiguousTrie::<i8>::new(16, 8);
+    let arr = format!("{:016b}", 300).into_bytes();
+    assert_eq!(trie.insert(-5, &arr), Ok(None));
+    assert_eq!(trie.insert(-6, &arr), Ok(Some(-5)));
+    assert_eq!(trie.get(&arr), Ok(Some(-6)));
+    assert_eq!(trie.get_optimistic(&arr), Ok(Some(-6)));
+}
+
+#[test]
+fn test_get_optimistic_during_updates() {
+    let trie = Arc::new(RwContiguousTrie::<usize>::new(16, 8));
+    // every value holds its key in the low 32 bits, so a torn read shows up as another key
+    let writers: Vec<_> = (0..2).map(|t| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            for round in 0..20 {
+                for i in (t..4096).step_by(2) {
+                    let arr = format!("{:016b}", i).into_bytes();
+                    trie.insert(round << 32 | i, &arr).unwrap();
+                }
+            }
+        })
+    }).collect();
+    let readers: Vec<_> = (0..2).map(|_| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            for _ in 0..10 {
+                for i in 0..4096 {
+                    let arr = format!("{:016b}", i).into_bytes();
+                    if let Some(value) = trie.get_optimistic(&arr).unwrap() {
+                        assert_eq!(value & 0xffff_ffff, i);
+                    }
+                }
+            }
+        })
+    }).collect();
+    for handle in writers.into_iter().chain(readers) {
+        handle.join().unwrap();
+    }
+    for i in 0..4096 {
+        let arr = format!("{:016b}", i).into_bytes();
+        assert_eq!(trie.get_optimistic(&arr), Ok(Some(19 << 32 | i)));
+    }
+}
+
+#[test]
+fn test_shrink_during_optimistic_reads() {
+    let trie = Arc::new(RwContiguousTrie::<usize>::new(16, 8));
+    // the writer keeps growing and shrinking the leaves, freeing the arrays the readers may be in
+    let writer = {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            for round in 0..20 {
+                for i in 0..4096 {
+                    let arr = format!("{:016b}", i).into_bytes();
+                    trie.insert(round << 32 | i, &arr).unwrap();
+                }
+                for i in 2048..4096 {
+                    let arr = format!("{:016b}", i).into_bytes();
+                    trie.remove(&arr).unwrap();
+                }
+                trie.shrink_to_fit();
+            }
+        })
+    };
+    let readers: Vec<_> = (0..2).map(|_| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            for _ in 0..10 {
+                for i in 0..4096 {
+                    let arr = format!("{:016b}", i).into_bytes();
+                    if let Some(value) = trie.get_optimistic(&arr).unwrap() {
+                        assert_eq!(value & 0xffff_ffff, i);
+                    }
+                }
+            }
+        })
+    }).collect();
+    for handle in Some(writer).into_iter().chain(readers) {
+        handle.join().unwrap();
+    }
+    assert_eq!(trie.get_optimistic(&format!("{:016b}", 2047).into_bytes()), Ok(Some(19 << 32 | 2047)));
+    assert_eq!(trie.get_optimistic(&format!("{:016b}", 2048).into_bytes()), Ok(None));
+}
+
+#[test]
+fn test_retired_arrays_are_freed_under_reads() {
+    let trie = Arc::new(RwContiguousTrie::<usize>::new(16, 8));
+    let stop = Arc::new(AtomicBool::new(false));
+    // readers that never stop reading, so the counters are hardly ever at zero all at once
+    let readers: Vec<_> = (0..2).map(|_| {
+        let trie = trie.clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                for i in 0..4096 {
+                    let arr = format!("{:016b}", i).into_bytes();
+                    if let Some(value) = trie.get_optimistic(&arr).unwrap() {
+                        assert_eq!(value, i);
+                    }
+                }
+            }
+        })
+    }).collect();
+
+    // every insert of a larger key grows the leaves and retires the array before
+    for i in 0..4096 {
+        let arr = format!("{:016b}", i).into_bytes();
+        trie.insert(i, &arr).unwrap();
+    }
+    let arr = format!("{:016b}", 0).into_bytes();
+    let mut writes = 0;
+    while trie.retired_arrays() > 0 && writes < 1000000 {
+        trie.insert(0, &arr).unwrap();
+        writes += 1;
+        if writes % 100 == 0 {
+            thread::yield_now();
+        }
+    }
+    assert_eq!(trie.retired_arrays(), 0);
+
+    stop.store(true, Ordering::Relaxed);
+    for handle in readers {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn test_values_that_are_not_pod() {
+    let trie = RwContiguousTrie::<&'static str>::new(16, 8);
+    let a = format!("{:016b}", 7).into_bytes();
+    let b = format!("{:016b}", 300).into_bytes();
+    assert_eq!(trie.insert("seven", &a), Ok(None));
+    assert_eq!(trie.insert("three hundred", &b), Ok(None));
+    assert_eq!(trie.insert("7", &a), Ok(Some("seven")));
+    assert_eq!(trie.get(&a), Ok(Some("7")));
+    assert_eq!(trie.remove(&b), Ok(Some("three hundred")));
+    assert_eq!(trie.get(&b), Ok(None));
+}
+
+const NTHREAD: usize = 8;
+
+#[test]
+fn test_racing_try_insert_has_one_winner() {
+    let trie = Arc::new(RwContiguousTrie::<usize>::new(16, 8));
+    let handles: Vec<_> = (0..NTHREAD).map(|t| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            // every thread goes through the same keys, from a different starting point
+            let mut won = vec![];
+            for j in 0..2048 {
+                let i = (j + t * 256) % 2048;
+                let arr = format!("{:016b}", i).into_bytes();
+                if trie.try_insert(t, &arr).is_ok() {
+                    won.push(i);
+                }
+            }
+            (t, won)
+        })
+    }).collect();
+
+    let mut winners = vec![None; 2048];
+    for handle in handles {
+        let (t, won) = handle.join().unwrap();
+        for i in won {
+            assert_eq!(winners[i], None, "key {} won twice", i);
+            winners[i] = Some(t);
+        }
+    }
+    for i in 0..2048 {
+        let arr = format!("{:016b}", i).into_bytes();
+        assert_eq!(trie.get(&arr), Ok(winners[i]));
+    }
+}
+
+#[test]
+fn test_racing_insert_and_remove_are_linearizable() {
+    let trie = Arc::new(RwContiguousTrie::<usize>::new(16, 8));
+    let handles: Vec<_> = (0..NTHREAD).map(|t| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            // (inserts that found the key absent, removes that found it present) per key
+            let mut counts = vec![(0, 0); 64];
+            for round in 0..500 {
+                for i in 0..64 {
+                    let arr = format!("{:016b}", i * 1021).into_bytes();
+                    if (round + t + i) % 3 == 0 {
+                        if trie.remove(&arr).unwrap().is_some() {
+                            counts[i].1 += 1;
+                        }
+                    } else if trie.insert(t, &arr).unwrap().is_none() {
+                        counts[i].0 += 1;
+                    }
+                }
+            }
+            counts
+        })
+    }).collect();
+
+    let mut totals = vec![(0, 0); 64];
+    for handle in handles {
+        for (i, (inserted, removed)) in handle.join().unwrap().into_iter().enumerate() {
+            totals[i].0 += inserted;
+            totals[i].1 += removed;
+        }
+    }
+    // every insert into an empty slot is undone by exactly one remove, unless the key is still there
+    for i in 0..64 {
+        let arr = format!("{:016b}", i * 1021).into_bytes();
+        let present = if trie.contain(&arr) { 1 } else { 0 };
+        assert_eq!(totals[i].0, totals[i].1 + present, "key {}", i);
+    }
+}
+
+#[test]
+fn test_racing_inserts_grow_the_leaves() {
+    let trie = Arc::new(RwContiguousTrie::<usize>::new(24, 8));
+    let handles: Vec<_> = (0..NTHREAD).map(|t| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            // the threads interleave their keys, so every insert may have to grow the leaves
+            for j in 0..4096 {
+                let i = j * NTHREAD + t;
+                let arr = format!("{:024b}", i).into_bytes();
+                assert_eq!(trie.insert(i, &arr), Ok(None));
+                assert_eq!(trie.get_optimistic(&arr), Ok(Some(i)));
+            }
+        })
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    for i in 0..4096 * NTHREAD {
+        let arr = format!("{:024b}", i).into_bytes();
+        assert_eq!(trie.get(&arr), Ok(Some(i)));
+    }
+}
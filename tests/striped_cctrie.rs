@@ -0,0 +1,87 @@
+#[macro_use]
+extern crate cchamt;
+
+use std::sync::Arc;
+use std::thread;
+use cchamt::{StripedContiguousTrie, TrieError};
+
+#[test]
+fn test_2_power_16_insert() {
+    let trie = StripedContiguousTrie::<usize>::new(32, 8);
+
+    for i in 0..65536 {
+        let arr = binary_format!(i).into_bytes();
+        trie.insert(i, &arr[2..]).unwrap();
+    }
+
+    for i in 0..65536 {
+        let arr = binary_format!(i).into_bytes();
+        assert_eq!(trie.get(&arr[2..]), Ok(Some(i)));
+    }
+    assert_eq!(trie.get(&binary_format!(65536).into_bytes()[2..]), Ok(None));
+}
+
+#[test]
+fn test_insert_returns_previous_value() {
+    let trie = StripedContiguousTrie::<usize>::new(32, 8);
+    let arr = binary_format!(42).into_bytes();
+
+    assert_eq!(trie.insert(1, &arr[2..]), Ok(None));
+    assert_eq!(trie.insert(2, &arr[2..]), Ok(Some(1)));
+    assert_eq!(trie.try_insert(3, &arr[2..]), Err(TrieError::Duplicate));
+    assert_eq!(trie.get(&arr[2..]), Ok(Some(2)));
+    assert_eq!(trie.remove(&arr[2..]), Ok(Some(2)));
+    assert!(!trie.contain(&arr[2..]));
+}
+
+#[test]
+fn test_malformed_keys_and_chunks() {
+    let trie = StripedContiguousTrie::<usize>::new(8, 4);
+    assert_eq!(trie.insert(1, "0001101".as_bytes()), Err(TrieError::WrongLength));
+    assert_eq!(trie.insert(1, "0001102a".as_bytes()), Err(TrieError::InvalidDigit));
+
+    // the 16 interior nodes, and no leaf until the first insert
+    assert_eq!(trie.allocated_slots(), 16);
+    assert_eq!(trie.get("00011010".as_bytes()), Ok(None));
+    assert_eq!(trie.insert(1, "00011010".as_bytes()), Ok(None));
+    assert_eq!(trie.insert(2, "11110000".as_bytes()), Ok(None));
+    assert_eq!(trie.allocated_slots(), 16 + 256);
+    assert_eq!(trie.get("11110000".as_bytes()), Ok(Some(2)));
+}
+
+#[test]
+fn test_concurrent_insert_and_get() {
+    let trie = Arc::new(StripedContiguousTrie::<usize>::new(24, 8));
+    let writers: Vec<_> = (0..4).map(|t| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            // the threads interleave their keys, so they race to allocate the same chunks
+            for i in 0..25000 {
+                let key = i * 4 + t;
+                let arr = binary_format!(key).into_bytes();
+                trie.insert(key, &arr[10..]).unwrap();
+            }
+        })
+    }).collect();
+    let readers: Vec<_> = (0..2).map(|_| {
+        let trie = trie.clone();
+        thread::spawn(move || {
+            for key in 0..100000 {
+                let arr = binary_format!(key).into_bytes();
+                match trie.get(&arr[10..]) {
+                    Ok(Some(value)) => assert_eq!(value, key),
+                    Ok(None) => {}
+                    Err(e) => panic!("{:?}", e),
+                }
+            }
+        })
+    }).collect();
+    for handle in writers.into_iter().chain(readers) {
+        handle.join().unwrap();
+    }
+
+    for key in 0..100000 {
+        let arr = binary_format!(key).into_bytes();
+        assert_eq!(trie.get(&arr[10..]), Ok(Some(key)));
+    }
+}